@@ -50,7 +50,7 @@ impl Product {
         )?;
         writer.add_field(9, Value::String(self.sku.clone()))?;
 
-        Ok(writer.build()?)
+        writer.build()
     }
 }
 
@@ -88,6 +88,6 @@ impl Order {
             Value::Array(self.tags.iter().map(|t| Value::String(t.clone())).collect()),
         )?;
 
-        Ok(writer.build()?)
+        writer.build()
     }
 }
@@ -3,7 +3,7 @@ mod data;
 use bytes::BytesMut;
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
 use data::{Order, Product};
-use imprint::{ImprintRecord, Merge, Project, Read, Write};
+use imprint::{DirectoryCache, ImprintRecord, Merge, Project, Read, Write};
 
 pub fn serde_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("serde");
@@ -25,6 +25,14 @@ pub fn serde_benchmark(c: &mut Criterion) {
             black_box(record);
         })
     });
+
+    let cache = DirectoryCache::new();
+    group.bench_function("deserialize_cached", |b| {
+        b.iter(|| {
+            let record = cache.read(buf.clone().freeze()).unwrap();
+            black_box(record);
+        })
+    });
 }
 
 pub fn ops_benchmark(c: &mut Criterion) {
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use bytes::Bytes;
+
+use crate::{
+    error::ImprintError,
+    limits::DecodeLimits,
+    serde::{Decoder, read_directory},
+    types::{DirectoryEntry, ImprintRecord, SchemaId},
+};
+
+/// Default maximum number of directories held by a [`DirectoryCache`]
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// Maximum number of distinct directories cached for a single schema id
+const DIRECTORIES_PER_SCHEMA: usize = 8;
+
+/// A shareable cache of parsed field directories keyed by schema id.
+///
+/// Records read through the cache share a single `Arc<[DirectoryEntry]>` with
+/// every other record of the same schema whose encoded directory is identical,
+/// which skips parsing and allocating the directory for high-volume streams of
+/// same-shaped records. Because directory entries carry payload offsets, a
+/// cached directory is only reused when the encoded bytes match exactly, so
+/// records with differently sized variable-width values have different
+/// directories. Up to eight directories are cached per schema id, and once
+/// those are taken other directories of that schema are parsed but not cached,
+/// rather than evicting directories that may still be hit.
+#[derive(Debug)]
+pub struct DirectoryCache {
+    capacity: usize,
+    directories: RwLock<Directories>,
+}
+
+#[derive(Debug, Default)]
+struct Directories {
+    by_schema: HashMap<SchemaId, Vec<CachedDirectory>>,
+    len: usize,
+}
+
+impl Directories {
+    /// Whether another directory of this schema may be cached
+    fn has_room(&self, schema_id: &SchemaId, capacity: usize) -> bool {
+        self.len < capacity
+            && self
+                .by_schema
+                .get(schema_id)
+                .is_none_or(|cached| cached.len() < DIRECTORIES_PER_SCHEMA)
+    }
+
+    fn find(&self, schema_id: &SchemaId, raw: &[u8]) -> Option<Arc<[DirectoryEntry]>> {
+        self.by_schema
+            .get(schema_id)?
+            .iter()
+            .find(|cached| cached.raw == raw)
+            .map(|cached| cached.entries.clone())
+    }
+}
+
+#[derive(Debug)]
+struct CachedDirectory {
+    raw: Bytes,
+    entries: Arc<[DirectoryEntry]>,
+}
+
+impl Default for DirectoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectoryCache {
+    /// Creates an empty cache holding up to [`DEFAULT_CACHE_CAPACITY`] directories
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Creates an empty cache holding up to `capacity` directories. Once the
+    /// cache is full, new directories are parsed but not cached.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            directories: RwLock::new(Directories::default()),
+        }
+    }
+
+    /// Read a record from the buffer, returning the record and number of bytes read
+    pub fn read(&self, bytes: Bytes) -> Result<(ImprintRecord, usize), ImprintError> {
+        self.read_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// Read a record from the buffer, enforcing the given limits, and
+    /// returning the record and number of bytes read
+    pub fn read_with_limits(
+        &self,
+        bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<(ImprintRecord, usize), ImprintError> {
+        Decoder::new(limits).read_record(bytes, Some(self))
+    }
+
+    /// Get the cached directory of a schema id whose encoded entries, without
+    /// the leading count, are exactly `raw`, if any
+    pub fn get(&self, schema_id: SchemaId, raw: &[u8]) -> Option<Arc<[DirectoryEntry]>> {
+        self.directories
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .find(&schema_id, raw)
+    }
+
+    /// The number of cached directories
+    pub fn len(&self) -> usize {
+        self.directories
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len
+    }
+
+    /// Whether the cache holds no directories
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all cached directories
+    pub fn clear(&self) {
+        *self.directories.write().unwrap_or_else(|e| e.into_inner()) = Directories::default();
+    }
+
+    /// Return the cached directory if it was encoded as `raw`, otherwise parse
+    /// `raw` and cache the result if there is room for it
+    pub(crate) fn get_or_parse(
+        &self,
+        schema_id: SchemaId,
+        raw: Bytes,
+    ) -> Result<Arc<[DirectoryEntry]>, ImprintError> {
+        let has_room = {
+            let directories = self.directories.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(entries) = directories.find(&schema_id, &raw) {
+                return Ok(entries);
+            }
+            directories.has_room(&schema_id, self.capacity)
+        };
+
+        let entries = read_directory(raw.clone())?;
+        if !has_room {
+            return Ok(entries);
+        }
+
+        let mut directories = self.directories.write().unwrap_or_else(|e| e.into_inner());
+        // another reader may have cached the same directory in the meantime
+        if let Some(entries) = directories.find(&schema_id, &raw) {
+            return Ok(entries);
+        }
+        if directories.has_room(&schema_id, self.capacity) {
+            // copy the raw directory so the cache doesn't keep the whole
+            // buffer of the record it was read from alive
+            directories
+                .by_schema
+                .entry(schema_id)
+                .or_default()
+                .push(CachedDirectory {
+                    raw: Bytes::copy_from_slice(&raw),
+                    entries: entries.clone(),
+                });
+            directories.len += 1;
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Read;
+    use crate::serde::{DIR_ENTRY_BYTES, HEADER_BYTES};
+    use crate::test_support::encode_record;
+
+    #[test]
+    fn should_share_directory_between_same_shaped_records() {
+        // Given two records with the same fixed-width fields
+        let cache = DirectoryCache::new();
+        let first = encode_record(&[(1, 42.into()), (2, 7i64.into())]);
+        let second = encode_record(&[(1, 43.into()), (2, 8i64.into())]);
+
+        // When reading both through the cache
+        let (first, _) = cache.read(first).unwrap();
+        let (second, _) = cache.read(second).unwrap();
+
+        // Then they should share the same directory allocation
+        assert!(Arc::ptr_eq(&first.directory, &second.directory));
+        assert_eq!(cache.len(), 1);
+
+        // And still read their own values
+        assert_eq!(first.get_value(1).unwrap(), Some(42.into()));
+        assert_eq!(second.get_value(1).unwrap(), Some(43.into()));
+    }
+
+    #[test]
+    fn should_not_reuse_directory_with_different_offsets() {
+        // Given two records with the same fields but different string lengths
+        let cache = DirectoryCache::new();
+        let short_bytes = encode_record(&[(1, "a".into()), (2, 1.into())]);
+        let long_bytes = encode_record(&[(1, "abc".into()), (2, 2.into())]);
+
+        // When reading both through the cache
+        let (short, _) = cache.read(short_bytes).unwrap();
+        let (long, _) = cache.read(long_bytes.clone()).unwrap();
+
+        // Then each should use its own offsets
+        assert_eq!(short.header.schema_id, long.header.schema_id);
+        assert!(!Arc::ptr_eq(&short.directory, &long.directory));
+        assert_eq!(short.get_value(2).unwrap(), Some(1.into()));
+        assert_eq!(long.get_value(2).unwrap(), Some(2.into()));
+
+        // And the result should match an uncached read
+        assert_eq!(long, ImprintRecord::read(long_bytes).unwrap().0);
+    }
+
+    #[test]
+    fn should_cache_several_directories_per_schema() {
+        // Given records of one schema alternating between string lengths
+        let cache = DirectoryCache::new();
+        let short = || encode_record(&[(1, "a".into()), (2, 1.into())]);
+        let long = || encode_record(&[(1, "abc".into()), (2, 2.into())]);
+
+        // When reading them through the cache
+        let (first_short, _) = cache.read(short()).unwrap();
+        let (first_long, _) = cache.read(long()).unwrap();
+        let (second_short, _) = cache.read(short()).unwrap();
+        let (second_long, _) = cache.read(long()).unwrap();
+
+        // Then each shape should keep hitting its own directory
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&first_short.directory, &second_short.directory));
+        assert!(Arc::ptr_eq(&first_long.directory, &second_long.directory));
+    }
+
+    #[test]
+    fn should_not_evict_directories_when_schema_is_full() {
+        // Given a record of one schema read through the cache
+        let cache = DirectoryCache::new();
+        let encode_len = |len: usize| encode_record(&[(1, "a".repeat(len).into()), (2, 1.into())]);
+        let (first, _) = cache.read(encode_len(1)).unwrap();
+
+        // When reading many more shapes of the same schema
+        for len in 2..=2 * DIRECTORIES_PER_SCHEMA {
+            cache.read(encode_len(len)).unwrap();
+        }
+
+        // Then only a bounded number should be cached, and the first still hit
+        assert_eq!(cache.len(), DIRECTORIES_PER_SCHEMA);
+        let (again, _) = cache.read(encode_len(1)).unwrap();
+        assert!(Arc::ptr_eq(&first.directory, &again.directory));
+    }
+
+    #[test]
+    fn should_not_grow_past_capacity() {
+        // Given a cache that holds a single directory
+        let cache = DirectoryCache::with_capacity(1);
+
+        // When reading records of two different schemas
+        let first_bytes = encode_record(&[(1, 1.into())]);
+        let second_bytes = encode_record(&[(2, 1.into())]);
+        let (first, _) = cache.read(first_bytes.clone()).unwrap();
+        let (second, _) = cache.read(second_bytes.clone()).unwrap();
+
+        // Then only the first schema should be cached
+        let raw_directory =
+            |bytes: &Bytes| bytes.slice(HEADER_BYTES + 1..HEADER_BYTES + 1 + DIR_ENTRY_BYTES);
+        assert_eq!(cache.len(), 1);
+        let cached = cache.get(first.header.schema_id, &raw_directory(&first_bytes));
+        assert!(cached.is_some_and(|entries| Arc::ptr_eq(&entries, &first.directory)));
+        assert!(
+            cache
+                .get(second.header.schema_id, &raw_directory(&second_bytes))
+                .is_none()
+        );
+        assert_eq!(second.get_value(2).unwrap(), Some(1.into()));
+    }
+}
@@ -0,0 +1,225 @@
+use bytes::BytesMut;
+
+use crate::{
+    diff::{RecordDiff, patch},
+    error::ImprintError,
+    serde::Write,
+    types::{DirectoryEntry, Flags, ImprintRecord, SchemaId, TypeCode, Value},
+};
+
+/// Field of a delta record holding the array of removed field ids
+pub const DELTA_REMOVED_FIELD: u16 = 1;
+/// Field of a delta record holding the row of added and changed fields
+pub const DELTA_CHANGES_FIELD: u16 = 2;
+/// Field of a delta record holding the schema hash of its base record
+pub const DELTA_BASE_FIELD: u16 = 3;
+
+impl RecordDiff {
+    /// Encode this diff as a delta record against a base record with the
+    /// given schema id.
+    ///
+    /// A delta record has the [`Flags::DELTA`] flag set and the fieldspace of
+    /// its base. Its payload holds the removed field ids as an array under
+    /// [`DELTA_REMOVED_FIELD`] (omitted when empty), the added and changed
+    /// fields as a row under [`DELTA_CHANGES_FIELD`] and the base schema hash
+    /// under [`DELTA_BASE_FIELD`].
+    pub fn to_delta(&self, base_schema_id: SchemaId) -> Result<ImprintRecord, ImprintError> {
+        let mut fields = Vec::with_capacity(3);
+        if !self.removed.is_empty() {
+            let ids = self.removed.iter().map(|&id| Value::Int32(id.into()));
+            fields.push((DELTA_REMOVED_FIELD, Value::Array(ids.collect())));
+        }
+        fields.push((
+            DELTA_CHANGES_FIELD,
+            Value::Row(Box::new(self.changes().clone())),
+        ));
+        fields.push((
+            DELTA_BASE_FIELD,
+            Value::Int64(base_schema_id.schema_hash.into()),
+        ));
+
+        let mut directory = Vec::with_capacity(fields.len());
+        let mut payload = BytesMut::new();
+        for (id, value) in fields {
+            directory.push(DirectoryEntry {
+                id,
+                type_code: value.type_code(),
+                offset: payload.len() as u32,
+            });
+            value.write(&mut payload)?;
+        }
+
+        Ok(ImprintRecord::from_parts(
+            Flags::DELTA,
+            base_schema_id.fieldspace_id,
+            directory,
+            payload.freeze(),
+        ))
+    }
+}
+
+impl ImprintRecord {
+    /// Encode this record as a delta against an older version of it, holding
+    /// only the fields that changed
+    pub fn delta_from(&self, base: &ImprintRecord) -> Result<ImprintRecord, ImprintError> {
+        base.diff(self)?.to_delta(base.header.schema_id)
+    }
+
+    /// Whether this record is a delta against a base record
+    pub fn is_delta(&self) -> bool {
+        self.header.flags.contains(Flags::DELTA)
+    }
+
+    /// The schema id of the base record a delta record was encoded against
+    pub fn delta_base_schema_id(&self) -> Result<SchemaId, ImprintError> {
+        if !self.is_delta() {
+            return Err(ImprintError::NotDelta);
+        }
+        let hash = self.require_i64(DELTA_BASE_FIELD)?;
+        let schema_hash = u32::try_from(hash)
+            .map_err(|_| ImprintError::SchemaError(format!("invalid base schema hash {hash}")))?;
+        Ok(SchemaId {
+            fieldspace_id: self.header.schema_id.fieldspace_id,
+            schema_hash,
+        })
+    }
+
+    /// Reconstruct a full record by applying a delta record to the base it
+    /// was encoded against. Unchanged fields are copied from the base and
+    /// changed fields from the delta without decoding either.
+    pub fn apply_delta(
+        base: &ImprintRecord,
+        delta: &ImprintRecord,
+    ) -> Result<ImprintRecord, ImprintError> {
+        let expected = delta.delta_base_schema_id()?;
+        if expected != base.header.schema_id {
+            return Err(ImprintError::DeltaBaseMismatch {
+                expected,
+                actual: base.header.schema_id,
+            });
+        }
+
+        let mut removed = match delta.get_value(DELTA_REMOVED_FIELD)? {
+            Some(Value::Array(ids)) => ids
+                .into_iter()
+                .map(|id| match id {
+                    Value::Int32(id) => u16::try_from(id).map_err(|_| {
+                        ImprintError::SchemaError(format!("invalid removed field id {id}"))
+                    }),
+                    other => Err(ImprintError::TypeMismatch {
+                        field_id: DELTA_REMOVED_FIELD,
+                        expected: TypeCode::Int32,
+                        actual: other.type_code(),
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(other) => {
+                return Err(ImprintError::TypeMismatch {
+                    field_id: DELTA_REMOVED_FIELD,
+                    expected: TypeCode::Array,
+                    actual: other.type_code(),
+                });
+            }
+            None => Vec::new(),
+        };
+        removed.sort_unstable();
+
+        let changes = delta.require_row(DELTA_CHANGES_FIELD)?;
+        patch(base, &changes, &removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::test_support::create_record;
+    use crate::{Read, Write};
+
+    fn create_versions() -> (ImprintRecord, ImprintRecord) {
+        let base = create_record(&[
+            (1, 42.into()),
+            (2, "a".repeat(100).into()),
+            (3, "pending".into()),
+            (4, true.into()),
+        ]);
+        let new = create_record(&[
+            (1, 42.into()),
+            (2, "a".repeat(100).into()),
+            (3, "processed".into()),
+            (5, 7i64.into()),
+        ]);
+        (base, new)
+    }
+
+    #[test]
+    fn should_reconstruct_record_from_delta() {
+        // Given two versions of a record
+        let (base, new) = create_versions();
+
+        // When encoding the new version as a delta and sending it over the wire
+        let delta = new.delta_from(&base).unwrap();
+        let mut buf = BytesMut::new();
+        delta.write(&mut buf).unwrap();
+        let (delta, _) = ImprintRecord::read(buf.freeze()).unwrap();
+
+        // Then it should reference the base schema
+        assert!(delta.is_delta());
+        assert_eq!(delta.delta_base_schema_id().unwrap(), base.schema_id());
+
+        // And its own schema hash should still be derived from its directory
+        assert_eq!(
+            delta.schema_id(),
+            SchemaId::for_directory(base.schema_id().fieldspace_id, &delta.directory)
+        );
+
+        // And only hold the changed fields
+        assert!(delta.payload.len() < new.payload.len());
+
+        // And applying it should reproduce the new version
+        assert_eq!(ImprintRecord::apply_delta(&base, &delta).unwrap(), new);
+    }
+
+    #[test]
+    fn should_omit_removed_ids_when_nothing_was_removed() {
+        // Given a record with a single changed field
+        let base = create_record(&[(1, 1.into()), (2, 2.into())]);
+        let new = base.with_field(2, 3.into()).unwrap();
+
+        // When encoding it as a delta
+        let delta = new.delta_from(&base).unwrap();
+
+        // Then only the changes should be present
+        assert!(!delta.contains(DELTA_REMOVED_FIELD));
+        assert_eq!(ImprintRecord::apply_delta(&base, &delta).unwrap(), new);
+    }
+
+    #[test]
+    fn should_reject_mismatched_base() {
+        // Given a delta against one version of a record
+        let (base, new) = create_versions();
+        let delta = new.delta_from(&base).unwrap();
+
+        // When applying it to a record with a different schema
+        let result = ImprintRecord::apply_delta(&new, &delta);
+
+        // Then it should be rejected
+        assert!(matches!(
+            result,
+            Err(ImprintError::DeltaBaseMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn should_reject_records_that_are_not_deltas() {
+        // Given two plain records
+        let (base, new) = create_versions();
+
+        // When applying one to the other as a delta
+        let result = ImprintRecord::apply_delta(&base, &new);
+
+        // Then it should be rejected
+        assert!(matches!(result, Err(ImprintError::NotDelta)));
+    }
+}
@@ -0,0 +1,326 @@
+use bytes::BytesMut;
+
+use crate::{
+    error::ImprintError,
+    types::{DirectoryEntry, Flags, ImprintRecord, TypeCode, Value},
+};
+
+/// The field-level differences between two records, as produced by
+/// [`ImprintRecord::diff`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordDiff {
+    /// Fields only present in the new record
+    pub added: Vec<u16>,
+    /// Fields only present in the old record
+    pub removed: Vec<u16>,
+    /// Fields present in both records with different types
+    pub type_changed: Vec<u16>,
+    /// Fields present in both records with the same type but different values
+    pub value_changed: Vec<u16>,
+    /// The added and changed fields, copied from the new record
+    changes: ImprintRecord,
+}
+
+impl RecordDiff {
+    /// Whether the two records hold the same fields and values
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.type_changed.is_empty()
+            && self.value_changed.is_empty()
+    }
+
+    /// The added and changed fields with their values in the new record
+    pub fn changes(&self) -> &ImprintRecord {
+        &self.changes
+    }
+
+    /// Decode the new value of an added or changed field
+    pub fn new_value(&self, field_id: u16) -> Result<Option<Value>, ImprintError> {
+        self.changes.get_value(field_id)
+    }
+
+    /// Apply this diff to the old record, producing the new one
+    pub fn apply(&self, old: &ImprintRecord) -> Result<ImprintRecord, ImprintError> {
+        patch(old, &self.changes, &self.removed)
+    }
+}
+
+/// Overwrite the fields of `old` with `changes` and drop the sorted `removed`
+/// ids, copying raw slices of both records. Fields of `changes` are copied as
+/// is, so tombstones among them are kept rather than applied.
+pub(crate) fn patch(
+    old: &ImprintRecord,
+    changes: &ImprintRecord,
+    removed: &[u16],
+) -> Result<ImprintRecord, ImprintError> {
+    let mut new_directory = Vec::with_capacity(old.directory.len() + changes.directory.len());
+    let mut new_payload = BytesMut::with_capacity(old.payload.len() + changes.payload.len());
+
+    let mut old_idx = 0;
+    let mut changes_idx = 0;
+    loop {
+        let field = match (
+            old.directory.get(old_idx),
+            changes.directory.get(changes_idx),
+        ) {
+            (Some(o), c) if c.is_none_or(|c| o.id < c.id) => {
+                old_idx += 1;
+                if removed.binary_search(&o.id).is_ok() {
+                    continue;
+                }
+                old.raw_field(old_idx - 1)?
+            }
+            (o, Some(c)) => {
+                // a changed field replaces the old one
+                if o.is_some_and(|o| o.id == c.id) {
+                    old_idx += 1;
+                }
+                changes_idx += 1;
+                changes.raw_field(changes_idx - 1)?
+            }
+            (_, None) => break,
+        };
+
+        new_directory.push(DirectoryEntry {
+            id: field.id,
+            type_code: field.type_code,
+            offset: new_payload.len() as u32,
+        });
+        new_payload.extend_from_slice(field.bytes);
+    }
+
+    Ok(ImprintRecord::from_parts(
+        old.header.flags.without(Flags::DELTA),
+        old.header.schema_id.fieldspace_id,
+        new_directory,
+        new_payload.freeze(),
+    ))
+}
+
+impl ImprintRecord {
+    /// Compare this (old) record against a newer version of it.
+    ///
+    /// Values are compared by their encoded bytes. Only values that may hold
+    /// maps are decoded when their bytes differ, so that equal maps written in
+    /// different orders aren't reported as changed.
+    pub fn diff(&self, other: &ImprintRecord) -> Result<RecordDiff, ImprintError> {
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut type_changed = Vec::new();
+        let mut value_changed = Vec::new();
+
+        let mut self_idx = 0;
+        let mut other_idx = 0;
+        while self_idx < self.directory.len() || other_idx < other.directory.len() {
+            match (self.directory.get(self_idx), other.directory.get(other_idx)) {
+                (Some(old), Some(new)) if old.id == new.id => {
+                    if old.type_code != new.type_code {
+                        type_changed.push(new.id);
+                    } else if self.raw_field(self_idx)? != other.raw_field(other_idx)?
+                        && !(may_reorder(new.type_code) && self.same_field(other, new.id)?)
+                    {
+                        value_changed.push(new.id);
+                    }
+                    self_idx += 1;
+                    other_idx += 1;
+                }
+                (Some(old), new) if new.is_none_or(|n| old.id < n.id) => {
+                    removed.push(old.id);
+                    self_idx += 1;
+                }
+                (_, new) => {
+                    added.extend(new.map(|n| n.id));
+                    other_idx += 1;
+                }
+            }
+        }
+
+        let mut changed = [added.as_slice(), &type_changed, &value_changed].concat();
+        changed.sort_unstable();
+        let changes = other.project_where(|id| changed.binary_search(&id).is_ok())?;
+
+        Ok(RecordDiff {
+            added,
+            removed,
+            type_changed,
+            value_changed,
+            changes,
+        })
+    }
+
+    /// Whether both records hold the same decoded value for a field
+    fn same_field(&self, other: &ImprintRecord, field_id: u16) -> Result<bool, ImprintError> {
+        match (self.get_value(field_id)?, other.get_value(field_id)?) {
+            (Some(a), Some(b)) => same_value(&a, &b),
+            (a, b) => Ok(a.is_none() && b.is_none()),
+        }
+    }
+}
+
+/// Whether values of this type may hold maps, whose entries can be encoded
+/// in any order
+fn may_reorder(type_code: TypeCode) -> bool {
+    matches!(type_code, TypeCode::Array | TypeCode::Map | TypeCode::Row)
+}
+
+/// Whether two decoded values are the same, ignoring map order and comparing
+/// floats bitwise so that `0.0` and `-0.0` differ
+fn same_value(a: &Value, b: &Value) -> Result<bool, ImprintError> {
+    Ok(match (a, b) {
+        (Value::Float32(a), Value::Float32(b)) => a.to_bits() == b.to_bits(),
+        (Value::Float64(a), Value::Float64(b)) => a.to_bits() == b.to_bits(),
+        (Value::Array(a), Value::Array(b)) => {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
+            for (a, b) in a.iter().zip(b) {
+                if !same_value(a, b)? {
+                    return Ok(false);
+                }
+            }
+            true
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
+            for (key, a) in a {
+                match b.get(key) {
+                    Some(b) if same_value(a, b)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            true
+        }
+        (Value::Row(a), Value::Row(b)) => a.diff(b)?.is_empty(),
+        (a, b) => a == b,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::test_support::{create_record, create_update};
+    use crate::{MapKey, TypeCode};
+
+    #[test]
+    fn should_classify_changed_fields() {
+        // Given two versions of a record
+        let old = create_record(&[
+            (1, 42.into()),
+            (2, "same".into()),
+            (3, "before".into()),
+            (4, 7.into()),
+            (5, true.into()),
+        ]);
+        let new = create_record(&[
+            (0, false.into()),
+            (1, 42.into()),
+            (2, "same".into()),
+            (3, "after".into()),
+            (4, 7i64.into()),
+            (6, 1.0f64.into()),
+        ]);
+
+        // When diffing them
+        let diff = old.diff(&new).unwrap();
+
+        // Then each field should be classified
+        assert_eq!(diff.added, vec![0, 6]);
+        assert_eq!(diff.removed, vec![5]);
+        assert_eq!(diff.type_changed, vec![4]);
+        assert_eq!(diff.value_changed, vec![3]);
+        assert!(!diff.is_empty());
+
+        // And the new values should be available
+        assert_eq!(diff.new_value(3).unwrap(), Some("after".into()));
+        assert_eq!(diff.new_value(2).unwrap(), None);
+    }
+
+    #[test]
+    fn should_apply_diff_as_patch() {
+        // Given two versions of a record
+        let old = create_record(&[(1, 42.into()), (2, "before".into()), (3, true.into())]);
+        let new = create_record(&[(1, 42.into()), (2, "after".into()), (4, 1i64.into())]);
+
+        // When applying their diff to the old record
+        let patched = old.diff(&new).unwrap().apply(&old).unwrap();
+
+        // Then the new record should be reproduced
+        assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn should_report_no_changes_for_equal_records() {
+        // Given two records with the same values
+        let record = create_record(&[(1, 42.into()), (2, "same".into())]);
+
+        // When diffing them
+        let diff = record.diff(&record.clone()).unwrap();
+
+        // Then the diff should be empty and a no-op
+        assert!(diff.is_empty());
+        assert_eq!(diff.changes().field_count(), 0);
+        assert_eq!(diff.apply(&record).unwrap(), record);
+    }
+
+    #[test]
+    fn should_report_changed_sign_of_zero() {
+        // Given two records differing only in the sign of a zero, at the top
+        // level and inside a map
+        let map = |v: f64| Value::Map([(MapKey::Int32(1), Value::Float64(v))].into());
+        let old = create_record(&[(1, 0.0f64.into()), (2, map(0.0))]);
+        let new = create_record(&[(1, (-0.0f64).into()), (2, map(-0.0))]);
+
+        // When diffing them
+        let diff = old.diff(&new).unwrap();
+
+        // Then both fields should be changed and the patch should reproduce them
+        assert_eq!(diff.value_changed, vec![1, 2]);
+        assert_eq!(diff.apply(&old).unwrap(), new);
+    }
+
+    #[test]
+    fn should_apply_diff_with_tombstones() {
+        // Given a new version of a record that carries tombstones
+        let old = create_record(&[(1, 42.into()), (2, "before".into()), (4, true.into())]);
+        let new = create_update(&[(1, 43.into()), (4, true.into())], &[2, 3]);
+
+        // When diffing and patching
+        let diff = old.diff(&new).unwrap();
+
+        // Then the tombstones should be reported as changes and kept by the patch
+        assert_eq!(diff.added, vec![3]);
+        assert_eq!(diff.type_changed, vec![2]);
+        assert_eq!(diff.apply(&old).unwrap(), new);
+    }
+
+    #[test]
+    fn should_not_report_equal_values_with_different_encodings() {
+        // Given a record holding a map
+        let map: HashMap<_, _> = [
+            (MapKey::Int32(1), Value::Int32(1)),
+            (MapKey::Int32(2), Value::Int32(2)),
+        ]
+        .into();
+        let old = create_record(&[(1, Value::Map(map))]);
+
+        // And the same map encoded with its entries in reverse order
+        let mut raw = old.get_raw_bytes(1).unwrap().to_vec();
+        let entries = raw.split_off(3);
+        let (first, second) = entries.split_at(8);
+        raw.extend_from_slice(second);
+        raw.extend_from_slice(first);
+        let new = old.with_raw_field(1, TypeCode::Map, raw.into()).unwrap();
+        assert_ne!(old.get_raw_bytes(1), new.get_raw_bytes(1));
+
+        // When diffing them
+        let diff = old.diff(&new).unwrap();
+
+        // Then the field should not be reported as changed
+        assert!(diff.is_empty());
+    }
+}
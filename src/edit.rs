@@ -0,0 +1,313 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    error::ImprintError,
+    ops::Project,
+    serde::Write,
+    types::{DirectoryEntry, ImprintRecord, TypeCode, Value},
+    validate::Violation,
+};
+
+impl ImprintRecord {
+    /// Return a copy of this record with the given field set to `value`,
+    /// replacing any existing value. Other fields are copied without decoding.
+    pub fn with_field(&self, id: u16, value: Value) -> Result<ImprintRecord, ImprintError> {
+        let mut buf = BytesMut::new();
+        value.write(&mut buf)?;
+        self.with_raw_field(id, value.type_code(), buf.freeze())
+    }
+
+    /// Return a copy of this record with the given field set to an already
+    /// encoded value, replacing any existing value.
+    ///
+    /// The bytes are spliced in as is, so they must be a complete encoding of
+    /// a single value of `type_code`. A tombstone has no encoding, so its bytes
+    /// must be empty.
+    pub fn with_raw_field(
+        &self,
+        id: u16,
+        type_code: TypeCode,
+        bytes: Bytes,
+    ) -> Result<ImprintRecord, ImprintError> {
+        // find the range of the payload being replaced, which is empty when
+        // inserting a new field
+        let (idx, next, replaced) = match self.directory.binary_search_by_key(&id, |e| e.id) {
+            Ok(idx) => (idx, idx + 1, self.field_range(idx)?),
+            Err(idx) if idx < self.directory.len() => {
+                let start = self.field_range(idx)?.start;
+                (idx, idx, start..start)
+            }
+            Err(idx) => (idx, idx, self.payload.len()..self.payload.len()),
+        };
+
+        if type_code == TypeCode::Tombstone && !bytes.is_empty() {
+            return Err(ImprintError::InvalidField {
+                field_id: id,
+                offset: replaced.start as u32,
+                violation: Violation::LengthMismatch {
+                    expected: bytes.len(),
+                    actual: 0,
+                },
+            });
+        }
+
+        let mut new_payload =
+            BytesMut::with_capacity(self.payload.len() - replaced.len() + bytes.len());
+        new_payload.extend_from_slice(&self.payload[..replaced.start]);
+        new_payload.extend_from_slice(&bytes);
+        new_payload.extend_from_slice(&self.payload[replaced.end..]);
+
+        // later fields move by the difference in size of the old and new values
+        let shift = |e: &DirectoryEntry| -> Result<DirectoryEntry, ImprintError> {
+            let invalid = || ImprintError::InvalidOffset {
+                field_id: e.id,
+                offset: e.offset,
+            };
+            let offset = (e.offset as usize)
+                .checked_sub(replaced.end)
+                .filter(|_| e.offset as usize <= self.payload.len())
+                .and_then(|after| (replaced.start + bytes.len()).checked_add(after))
+                .ok_or_else(invalid)?;
+            Ok(DirectoryEntry {
+                offset: u32::try_from(offset).map_err(|_| invalid())?,
+                ..e.clone()
+            })
+        };
+        let mut new_directory = Vec::with_capacity(self.directory.len() + 1);
+        new_directory.extend_from_slice(&self.directory[..idx]);
+        new_directory.push(DirectoryEntry {
+            id,
+            type_code,
+            offset: replaced.start as u32,
+        });
+        for entry in &self.directory[next..] {
+            new_directory.push(shift(entry)?);
+        }
+
+        Ok(ImprintRecord::from_parts(
+            self.header.flags,
+            self.header.schema_id.fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+
+    /// Return a copy of this record without the given field. Other fields are
+    /// copied without decoding.
+    pub fn without_field(&self, id: u16) -> Result<ImprintRecord, ImprintError> {
+        self.project_where(|field_id| field_id != id)
+    }
+
+    /// Return a copy of this record without any of the given fields. Other
+    /// fields are copied without decoding.
+    pub fn without_fields(&self, ids: &[u16]) -> Result<ImprintRecord, ImprintError> {
+        self.drop_fields(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::create_record;
+    use crate::{Read, ValidationLevel};
+
+    fn create_test_record() -> ImprintRecord {
+        create_record(&[
+            (1, 42.into()),
+            (3, "hello".into()),
+            (5, true.into()),
+            (7, vec![1, 2, 3].into()),
+        ])
+    }
+
+    #[test]
+    fn should_replace_existing_field() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When replacing a variable-width field with a longer value
+        let updated = record.with_field(3, "hello world".into()).unwrap();
+
+        // Then it should match a record written with the new value
+        assert_eq!(
+            updated,
+            create_record(&[
+                (1, 42.into()),
+                (3, "hello world".into()),
+                (5, true.into()),
+                (7, vec![1, 2, 3].into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_replace_field_with_different_type() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When replacing a field with a shorter value of another type
+        let updated = record.with_field(7, Value::Null).unwrap();
+
+        // Then the new type should be recorded in the directory
+        assert_eq!(updated.get_value(7).unwrap(), Some(Value::Null));
+        assert_eq!(updated.get_value(5).unwrap(), Some(true.into()));
+        assert_ne!(updated.schema_id(), record.schema_id());
+    }
+
+    #[test]
+    fn should_insert_new_fields() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When inserting fields before, between and after the existing ones
+        let updated = record
+            .with_field(0, 1i64.into())
+            .unwrap()
+            .with_field(4, "new".into())
+            .unwrap()
+            .with_field(9, false.into())
+            .unwrap();
+
+        // Then it should match a record written with all of the fields
+        assert_eq!(
+            updated,
+            create_record(&[
+                (0, 1i64.into()),
+                (1, 42.into()),
+                (3, "hello".into()),
+                (4, "new".into()),
+                (5, true.into()),
+                (7, vec![1, 2, 3].into()),
+                (9, false.into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_splice_raw_bytes() {
+        // Given a record and the raw encoding of another record's field
+        let record = create_test_record();
+        let source = create_record(&[(3, "raw".into())]);
+        let raw = source.get_raw_bytes(3).unwrap();
+
+        // When splicing the raw bytes in under a new id
+        let updated = record.with_raw_field(2, TypeCode::String, raw).unwrap();
+
+        // Then the value should be readable along with the existing fields
+        assert_eq!(updated.get_value(2).unwrap(), Some("raw".into()));
+        assert_eq!(updated.get_value(3).unwrap(), Some("hello".into()));
+    }
+
+    #[test]
+    fn should_set_field_on_empty_record() {
+        // Given an empty record
+        let record = create_record(&[]);
+
+        // When setting a field
+        let updated = record.with_field(1, 42.into()).unwrap();
+
+        // Then it should match a record written with that field
+        assert_eq!(updated, create_record(&[(1, 42.into())]));
+    }
+
+    #[test]
+    fn should_remove_single_field() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When removing a field from the middle
+        let updated = record.without_field(3).unwrap();
+
+        // Then it should match a record written without that field
+        assert_eq!(
+            updated,
+            create_record(&[(1, 42.into()), (5, true.into()), (7, vec![1, 2, 3].into())])
+        );
+
+        // And removing a missing field should leave the record unchanged
+        assert_eq!(record.without_field(99).unwrap(), record);
+    }
+
+    #[test]
+    fn should_remove_several_fields() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When removing several fields at once
+        let updated = record.without_fields(&[7, 1, 99]).unwrap();
+
+        // Then it should match a record written without those fields
+        assert_eq!(
+            updated,
+            create_record(&[(3, "hello".into()), (5, true.into())])
+        );
+        assert_eq!(updated.header.payload_size as usize, updated.payload.len());
+    }
+
+    #[test]
+    fn should_reject_offsets_past_the_payload() {
+        // Given an encoded record with its last offset rewritten past the payload
+        let mut buf = BytesMut::new();
+        create_test_record().write(&mut buf).unwrap();
+        let last_offset = 16 + 3 * 7 + 3;
+        buf[last_offset..last_offset + 4].copy_from_slice(&99u32.to_le_bytes());
+        let (record, _) = ImprintRecord::read(buf.freeze()).unwrap();
+
+        // When setting fields before and at the corrupted entry
+        let inserted = record.with_field(6, 1.into());
+        let replaced = record.with_field(7, 1.into());
+
+        // Then the bad offset should be reported rather than panicking
+        assert!(matches!(
+            inserted,
+            Err(ImprintError::InvalidOffset {
+                field_id: 7,
+                offset: 99
+            })
+        ));
+        assert!(matches!(
+            replaced,
+            Err(ImprintError::InvalidOffset {
+                field_id: 7,
+                offset: 99
+            })
+        ));
+    }
+
+    #[test]
+    fn should_reject_tombstones_with_bytes() {
+        // Given a record
+        let record = create_test_record();
+
+        // When splicing in tombstones with and without a payload
+        let with_bytes = record.with_raw_field(2, TypeCode::Tombstone, Bytes::from_static(&[1]));
+        let empty = record
+            .with_raw_field(3, TypeCode::Tombstone, Bytes::new())
+            .unwrap();
+
+        // Then only the empty tombstone should be accepted
+        assert!(matches!(
+            with_bytes,
+            Err(ImprintError::InvalidField {
+                field_id: 2,
+                violation: Violation::LengthMismatch { .. },
+                ..
+            })
+        ));
+        assert_eq!(empty.get_value(3).unwrap(), None);
+        empty.validate(ValidationLevel::Deep).unwrap();
+    }
+
+    #[test]
+    fn should_undo_an_insert() {
+        // Given a record with a field set on it
+        let record = create_test_record();
+        let updated = record.with_field(4, "new".into()).unwrap();
+
+        // When removing that field again
+        let restored = updated.without_field(4).unwrap();
+
+        // Then the original record should be restored
+        assert_eq!(restored, record);
+    }
+}
@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+use crate::types::{SchemaId, TypeCode};
+use crate::validate::Violation;
+
 #[derive(Error, Debug)]
 pub enum ImprintError {
     #[error("invalid magic byte: expected 0x49, got {0:#x}")]
@@ -17,12 +20,50 @@ pub enum ImprintError {
     #[error("field not found: {0}")]
     FieldNotFound(u32),
 
+    #[error("type mismatch for field {field_id}: expected {expected:?}, got {actual:?}")]
+    TypeMismatch {
+        field_id: u16,
+        expected: TypeCode,
+        actual: TypeCode,
+    },
+
     #[error("invalid utf8 in string field")]
     InvalidUtf8String,
 
     #[error("buffer underflow: needed {needed} bytes, had {available}")]
     BufferUnderflow { needed: usize, available: usize },
 
+    #[error("decode limit exceeded: {limit} (max {max})")]
+    LimitExceeded { limit: &'static str, max: usize },
+
+    #[error("invalid offset {offset} for field {field_id}")]
+    InvalidOffset { field_id: u16, offset: u32 },
+
+    #[error("invalid field {field_id} at offset {offset}: {violation}")]
+    InvalidField {
+        field_id: u16,
+        offset: u32,
+        violation: Violation,
+    },
+
+    #[error("field {0} is present in both merged records")]
+    MergeConflict(u16),
+
+    #[error("duplicate field id: {0}")]
+    DuplicateFieldId(u16),
+
+    #[error("record is not a delta")]
+    NotDelta,
+
+    #[error("delta was encoded against schema {expected:?}, but the base has schema {actual:?}")]
+    DeltaBaseMismatch {
+        expected: SchemaId,
+        actual: SchemaId,
+    },
+
+    #[error("path segment {segment} can't be applied to a value of type {actual:?}")]
+    InvalidPath { segment: usize, actual: TypeCode },
+
     #[error("schema error: {0}")]
     SchemaError(String),
 
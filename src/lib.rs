@@ -1,16 +1,41 @@
+mod cache;
+mod delta;
+mod diff;
+mod edit;
 mod error;
+mod limits;
 mod ops;
+mod path;
+mod predicate;
+mod reshape;
 mod serde;
+#[cfg(test)]
+mod test_support;
 mod types;
+mod validate;
+mod value_ref;
 mod varint;
 mod writer;
 
+pub use cache::{DEFAULT_CACHE_CAPACITY, DirectoryCache};
+pub use delta::{DELTA_BASE_FIELD, DELTA_CHANGES_FIELD, DELTA_REMOVED_FIELD};
+pub use diff::RecordDiff;
 pub use error::ImprintError;
-pub use ops::{Merge, Project};
-pub use serde::{Read, Write};
+pub use limits::{DEFAULT_MAX_ALLOCATION, DEFAULT_MAX_DEPTH, DecodeLimits};
+pub use ops::{
+    ArrayMerge, ConflictResolver, ConflictStrategy, Merge, MergeOptions, Project, ProjectionSpec,
+    RawField, Side, VersionSource,
+};
+pub use path::PathSegment;
+pub use predicate::Predicate;
+pub use reshape::{FieldAssigner, Partitioner};
+pub use serde::{Read, ValueRead, Write};
 pub use types::{
-    DirectoryEntry, Flags, Header, ImprintRecord, MAGIC, SchemaId, TypeCode, VERSION, Value,
+    DirectoryEntry, Flags, Header, ImprintRecord, MAGIC, MapKey, SchemaId, TypeCode, VERSION,
+    Value, schema_hash,
 };
+pub use validate::{ValidationLevel, Violation};
+pub use value_ref::{ArrayIter, ArrayRef, MapIter, MapRef, RowRef, ValueRef};
 pub use varint::{decode as decode_varint, encode as encode_varint};
 pub use writer::ImprintWriter;
 
@@ -0,0 +1,62 @@
+use crate::error::ImprintError;
+
+/// Default maximum nesting depth of `Row`, `Array` and `Map` values
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// Default maximum number of bytes allocated while decoding a single value or record
+pub const DEFAULT_MAX_ALLOCATION: usize = 1 << 30;
+
+/// Resource limits enforced while decoding records and values.
+///
+/// Every length, count and type code in an encoded record comes from the
+/// producer of the record, so decoding untrusted input should bound how deep
+/// and how large the decoded values may get. Exceeding any limit fails with
+/// [`ImprintError::LimitExceeded`](crate::ImprintError::LimitExceeded).
+///
+/// The default limits only bound the nesting depth and the total allocation,
+/// and are applied by the plain `read` functions. Borrowed
+/// [`ValueRef`](crate::ValueRef) reads enforce every limit but
+/// `max_allocation`, since they never allocate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting depth of `Row`, `Array` and `Map` values
+    pub max_depth: usize,
+    /// Maximum number of elements in a single array, map or field directory
+    pub max_elements: usize,
+    /// Maximum number of bytes allocated while decoding a single value or record
+    pub max_allocation: usize,
+    /// Maximum length of a single `String` or `Bytes` value
+    pub max_bytes_len: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_elements: usize::MAX,
+            max_allocation: DEFAULT_MAX_ALLOCATION,
+            max_bytes_len: usize::MAX,
+        }
+    }
+}
+
+impl DecodeLimits {
+    pub(crate) fn check_elements(&self, count: u32) -> Result<(), ImprintError> {
+        if count as usize > self.max_elements {
+            return Err(ImprintError::LimitExceeded {
+                limit: "max_elements",
+                max: self.max_elements,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_bytes_len(&self, len: u32) -> Result<(), ImprintError> {
+        if len as usize > self.max_bytes_len {
+            return Err(ImprintError::LimitExceeded {
+                limit: "max_bytes_len",
+                max: self.max_bytes_len,
+            });
+        }
+        Ok(())
+    }
+}
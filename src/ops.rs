@@ -1,12 +1,76 @@
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
 use crate::{
     error::ImprintError,
-    types::{DirectoryEntry, Header, ImprintRecord, SchemaId},
+    serde::{ValueRead, Write},
+    types::{DirectoryEntry, Flags, Header, ImprintRecord, MapKey, SchemaId, TypeCode, Value},
+    value_ref::{RowRef, ValueRef},
+    varint,
 };
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 
 pub trait Project {
+    /// Keep only the given fields, ignoring ids that aren't present
     fn project(&self, field_ids: &[u16]) -> Result<ImprintRecord, ImprintError>;
+
+    /// Keep every field except the given ones, ignoring ids that aren't present
+    fn drop_fields(&self, field_ids: &[u16]) -> Result<ImprintRecord, ImprintError>;
+
+    /// Keep only the fields named by the `(from_id, to_id)` pairs, renumbering
+    /// them into the target fieldspace. Source ids that aren't present are
+    /// ignored, and mapping two fields to the same target id is an error.
+    fn project_remap(
+        &self,
+        mapping: &[(u16, u16)],
+        target_fieldspace_id: u32,
+    ) -> Result<ImprintRecord, ImprintError>;
+
+    /// Keep only the fields named by the spec, rebuilding nested rows with
+    /// only their requested sub-fields. Nested rows are rewritten by copying
+    /// slices of their payloads, without decoding any values.
+    fn project_nested(&self, spec: &ProjectionSpec) -> Result<ImprintRecord, ImprintError>;
+}
+
+/// Which fields to keep when projecting with [`Project::project_nested`],
+/// such as `{3: *, 7: {1, 4}}`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectionSpec {
+    /// The fields to keep, mapped to `None` to keep the whole value or to the
+    /// fields to keep within a nested row
+    pub fields: BTreeMap<u16, Option<ProjectionSpec>>,
+}
+
+impl ProjectionSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the whole value of a field
+    pub fn field(mut self, id: u16) -> Self {
+        self.fields.insert(id, None);
+        self
+    }
+
+    /// Keep only some fields of a nested row
+    pub fn nested(mut self, id: u16, spec: ProjectionSpec) -> Self {
+        self.fields.insert(id, Some(spec));
+        self
+    }
 }
+
+impl FromIterator<u16> for ProjectionSpec {
+    fn from_iter<I: IntoIterator<Item = u16>>(ids: I) -> Self {
+        Self {
+            fields: ids.into_iter().map(|id| (id, None)).collect(),
+        }
+    }
+}
+
 impl Project for ImprintRecord {
     fn project(&self, field_ids: &[u16]) -> Result<ImprintRecord, ImprintError> {
         // Sort and deduplicate the field IDs for efficient matching with sorted directory
@@ -18,9 +82,10 @@ impl Project for ImprintRecord {
         // payload buffer until we know the final size (zero copy makes a significant
         // difference here)
         let mut new_directory = Vec::with_capacity(sorted_field_ids.len());
-        let mut ranges = Vec::new();
+        let mut ranges: Vec<Range<usize>> = Vec::new();
 
-        // iterate through the directory fields and compute ranges to copy over
+        // walk the directory and the requested ids together, stopping as soon
+        // as either runs out
         let mut field_ids_idx = 0;
         let mut directory_idx = 0;
         let mut current_offset = 0;
@@ -28,126 +93,866 @@ impl Project for ImprintRecord {
         while directory_idx < self.directory.len() && field_ids_idx < sorted_field_ids.len() {
             let field = &self.directory[directory_idx];
 
-            if field.id == sorted_field_ids[field_ids_idx] {
-                // Get the next field's offset to determine this field's length
-                // we can't just use get_raw_bytes here because the field may
-                // start with a length prefix
-                let next_offset = if directory_idx + 1 < self.directory.len() {
-                    self.directory[directory_idx + 1].offset
-                } else {
-                    self.payload.len() as u32
-                };
+            match field.id.cmp(&sorted_field_ids[field_ids_idx]) {
+                Ordering::Less => directory_idx += 1,
+                // the requested id isn't present
+                Ordering::Greater => field_ids_idx += 1,
+                Ordering::Equal => {
+                    // The field spans up to the next field's offset
+                    let range = self.field_range(directory_idx)?;
+                    let field_length = range.len() as u32;
+
+                    new_directory.push(DirectoryEntry {
+                        id: field.id,
+                        type_code: field.type_code,
+                        offset: current_offset,
+                    });
+                    current_offset += field_length;
+
+                    // adjacent fields are copied in one go
+                    match ranges.last_mut() {
+                        Some(last) if last.end == range.start => last.end = range.end,
+                        _ => ranges.push(range),
+                    }
+                    field_ids_idx += 1;
+                    directory_idx += 1;
+                }
+            }
+        }
+
+        let mut new_payload = BytesMut::with_capacity(current_offset as usize);
+        for range in ranges {
+            new_payload.extend_from_slice(&self.payload[range]);
+        }
+
+        Ok(ImprintRecord::from_parts(
+            self.header.flags,
+            self.header.schema_id.fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+
+    fn drop_fields(&self, field_ids: &[u16]) -> Result<ImprintRecord, ImprintError> {
+        let mut sorted_field_ids = field_ids.to_vec();
+        sorted_field_ids.sort_unstable();
+        sorted_field_ids.dedup();
+
+        self.project_where(|id| sorted_field_ids.binary_search(&id).is_err())
+    }
 
-                let field_length = next_offset - field.offset;
+    fn project_remap(
+        &self,
+        mapping: &[(u16, u16)],
+        target_fieldspace_id: u32,
+    ) -> Result<ImprintRecord, ImprintError> {
+        let mut remapped = Vec::with_capacity(mapping.len());
+        for &(from, to) in mapping {
+            if let Ok(idx) = self.directory.binary_search_by_key(&from, |e| e.id) {
+                remapped.push((to, idx));
+            }
+        }
+        remapped.sort_unstable_by_key(|&(to, _)| to);
+        if let Some(w) = remapped.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(ImprintError::DuplicateFieldId(w[0].0));
+        }
 
-                new_directory.push(DirectoryEntry {
-                    id: field.id,
-                    type_code: field.type_code,
-                    offset: current_offset,
+        let mut new_directory = Vec::with_capacity(remapped.len());
+        let mut ranges = Vec::with_capacity(remapped.len());
+        let mut current_offset = 0;
+        for (to, idx) in remapped {
+            let range = self.field_range(idx)?;
+            new_directory.push(DirectoryEntry {
+                id: to,
+                type_code: self.directory[idx].type_code,
+                offset: current_offset,
+            });
+            current_offset += range.len() as u32;
+            ranges.push(range);
+        }
+
+        let mut new_payload = BytesMut::with_capacity(current_offset as usize);
+        for range in ranges {
+            new_payload.extend_from_slice(&self.payload[range]);
+        }
+
+        Ok(ImprintRecord::from_parts(
+            self.header.flags,
+            target_fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+
+    fn project_nested(&self, spec: &ProjectionSpec) -> Result<ImprintRecord, ImprintError> {
+        let (new_directory, new_payload) = project_spec(&self.directory, &self.payload, spec)?;
+        Ok(ImprintRecord::from_parts(
+            self.header.flags,
+            self.header.schema_id.fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+}
+
+/// Copy the fields of a directory and its payload named by the spec, rewriting
+/// nested rows that have a spec of their own
+fn project_spec(
+    directory: &[DirectoryEntry],
+    payload: &[u8],
+    spec: &ProjectionSpec,
+) -> Result<(Vec<DirectoryEntry>, BytesMut), ImprintError> {
+    let mut new_directory = Vec::with_capacity(spec.fields.len().min(directory.len()));
+    let mut new_payload = BytesMut::new();
+    for (idx, entry) in directory.iter().enumerate() {
+        let Some(nested) = spec.fields.get(&entry.id) else {
+            continue;
+        };
+        let end = directory
+            .get(idx + 1)
+            .map_or(payload.len(), |e| e.offset as usize);
+        let bytes = payload
+            .get(entry.offset as usize..end)
+            .ok_or(ImprintError::InvalidOffset {
+                field_id: entry.id,
+                offset: entry.offset,
+            })?;
+
+        new_directory.push(DirectoryEntry {
+            offset: new_payload.len() as u32,
+            ..entry.clone()
+        });
+        match nested {
+            Some(nested) if entry.type_code == TypeCode::Row => {
+                project_row(bytes, nested, &mut new_payload)?
+            }
+            Some(_) if entry.type_code != TypeCode::Tombstone => {
+                return Err(ImprintError::TypeMismatch {
+                    field_id: entry.id,
+                    expected: TypeCode::Row,
+                    actual: entry.type_code,
                 });
+            }
+            _ => new_payload.extend_from_slice(bytes),
+        }
+    }
+    Ok((new_directory, new_payload))
+}
+
+/// Write a copy of the encoded row at the start of `bytes` holding only the
+/// fields named by the spec
+fn project_row(
+    bytes: &[u8],
+    spec: &ProjectionSpec,
+    buf: &mut BytesMut,
+) -> Result<(), ImprintError> {
+    let (row, _) = RowRef::read(bytes)?;
+    let (directory, payload) = project_spec(&row.directory_entries()?, row.payload(), spec)?;
+    let header = Header {
+        flags: row.flags(),
+        schema_id: SchemaId::for_directory(row.schema_id().fieldspace_id, &directory),
+        payload_size: payload.len() as u32,
+    };
+    header.write(buf)?;
+    varint::encode(directory.len() as u32, buf);
+    for entry in &directory {
+        entry.write(buf)?;
+    }
+    buf.extend_from_slice(&payload);
+    Ok(())
+}
+
+impl ImprintRecord {
+    /// Copy the fields whose ids match `keep` into a new record without
+    /// decoding any values
+    pub(crate) fn project_where(
+        &self,
+        keep: impl Fn(u16) -> bool,
+    ) -> Result<ImprintRecord, ImprintError> {
+        // we do all this shenanigans with the ranges to avoid allocating a new
+        // payload buffer until we know the final size (zero copy makes a significant
+        // difference here)
+        let mut new_directory = Vec::new();
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut current_offset = 0;
 
-                ranges.push((field.offset, next_offset));
-                current_offset += field_length;
-                field_ids_idx += 1;
+        // iterate through the directory fields and compute ranges to copy over
+        for (directory_idx, field) in self.directory.iter().enumerate() {
+            if !keep(field.id) {
+                continue;
             }
 
-            directory_idx += 1;
+            // The field spans up to the next field's offset
+            let range = self.field_range(directory_idx)?;
+            let field_length = range.len() as u32;
+
+            new_directory.push(DirectoryEntry {
+                id: field.id,
+                type_code: field.type_code,
+                offset: current_offset,
+            });
+            current_offset += field_length;
+
+            // adjacent fields are copied in one go
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
         }
 
         let mut new_payload = BytesMut::with_capacity(current_offset as usize);
         for range in ranges {
-            new_payload.extend_from_slice(&self.payload[range.0 as usize..range.1 as usize]);
+            new_payload.extend_from_slice(&self.payload[range]);
         }
 
-        Ok(ImprintRecord {
-            header: Header {
-                flags: self.header.flags,
-                schema_id: SchemaId {
-                    fieldspace_id: self.header.schema_id.fieldspace_id,
-                    schema_hash: 0xdeadbeef, // TODO: compute the correct schema hash
-                },
-                payload_size: new_payload.len() as u32,
-            },
-            directory: new_directory,
-            payload: new_payload.freeze(),
+        Ok(ImprintRecord::from_parts(
+            self.header.flags,
+            self.header.schema_id.fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+}
+
+/// A field's id, type and encoded bytes, as seen by a merge conflict resolver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawField<'a> {
+    pub id: u16,
+    pub type_code: TypeCode,
+    pub bytes: &'a [u8],
+}
+
+/// Which record's field a conflict resolves to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A user callback receiving the left and right fields sharing an id
+pub type ConflictResolver =
+    dyn Fn(&RawField<'_>, &RawField<'_>) -> Result<Side, ImprintError> + Send + Sync;
+
+/// How a merge handles a field id present in both records
+#[derive(Clone, Default)]
+pub enum ConflictStrategy {
+    /// Keep the field from the left record
+    #[default]
+    PreferLeft,
+    /// Keep the field from the right record
+    PreferRight,
+    /// Fail with [`ImprintError::MergeConflict`]
+    ErrorOnConflict,
+    /// Keep the field from the left record, but fail with
+    /// [`ImprintError::TypeMismatch`] if the two fields have different types.
+    /// A tombstone never mismatches the field it deletes.
+    ErrorOnTypeMismatch,
+    /// Let a callback pick a side
+    Custom(Arc<ConflictResolver>),
+    /// Keep the field with the greater version, breaking ties by comparing
+    /// type codes and then encoded bytes, so that merging the same records in
+    /// any order gives the same result (see [`VersionSource`] for caveats).
+    ///
+    /// Tombstones take part in the comparison like any other field and are
+    /// kept in the merged record, so that a deletion still wins against older
+    /// writes merged in later.
+    LastWriterWins(VersionSource),
+}
+
+/// Where [`ConflictStrategy::LastWriterWins`] reads field versions from.
+/// Fields without a version lose to any field with one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSource {
+    /// Every field of a record is versioned by the record's `Int64` timestamp
+    /// in the given field.
+    ///
+    /// A merged record only keeps the greatest timestamp, which no longer
+    /// versions the older fields it holds, so results are only independent of
+    /// order when all of the original records are merged at once with
+    /// [`ImprintRecord::merge_all_with`].
+    RecordTimestamp(u16),
+    /// Each field is versioned by its entry in the given `Map` field, which
+    /// maps `Int32` field ids to `Int64` versions. The maps of merged records
+    /// are combined by keeping the greatest version of each field.
+    FieldVersions(u16),
+}
+
+impl VersionSource {
+    /// The version of a field in a record
+    fn version(&self, record: &ImprintRecord, field_id: u16) -> Result<Option<i64>, ImprintError> {
+        let versions_id = match *self {
+            Self::RecordTimestamp(timestamp_id) => return record.get_i64(timestamp_id),
+            Self::FieldVersions(versions_id) => versions_id,
+        };
+        let versions = match record.get_value_ref(versions_id)? {
+            Some(ValueRef::Map(versions)) => versions,
+            Some(other) => return Err(versions_mismatch(versions_id, other.type_code())),
+            None => return Ok(None),
+        };
+        for entry in versions.iter() {
+            match entry? {
+                (ValueRef::Int32(id), ValueRef::Int64(version)) if id == field_id.into() => {
+                    return Ok(Some(version));
+                }
+                (ValueRef::Int32(_), ValueRef::Int64(_)) => {}
+                _ => return Err(invalid_versions(versions_id)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Combine the version maps of the given records by keeping the greatest
+    /// version of each field, returning the id and encoding of the combined
+    /// map when more than one record holds one
+    fn merged_versions(
+        &self,
+        records: &[&ImprintRecord],
+    ) -> Result<Option<(u16, Bytes)>, ImprintError> {
+        let Self::FieldVersions(versions_id) = *self else {
+            return Ok(None);
+        };
+
+        let mut merged: HashMap<MapKey, Value> = HashMap::new();
+        let mut count = 0;
+        for record in records {
+            let versions = match record.get_value(versions_id)? {
+                Some(Value::Map(versions)) => versions,
+                Some(other) => return Err(versions_mismatch(versions_id, other.type_code())),
+                None => continue,
+            };
+            count += 1;
+            for (id, version) in versions {
+                let (MapKey::Int32(_), Value::Int64(version)) = (&id, version) else {
+                    return Err(invalid_versions(versions_id));
+                };
+                merged
+                    .entry(id)
+                    .and_modify(|v| {
+                        if let Value::Int64(current) = v {
+                            *current = (*current).max(version);
+                        }
+                    })
+                    .or_insert(Value::Int64(version));
+            }
+        }
+        if count < 2 {
+            return Ok(None);
+        }
+
+        let mut buf = BytesMut::new();
+        Value::Map(merged).write(&mut buf)?;
+        Ok(Some((versions_id, buf.freeze())))
+    }
+}
+
+fn versions_mismatch(field_id: u16, actual: TypeCode) -> ImprintError {
+    ImprintError::TypeMismatch {
+        field_id,
+        expected: TypeCode::Map,
+        actual,
+    }
+}
+
+fn invalid_versions(field_id: u16) -> ImprintError {
+    ImprintError::SchemaError(format!(
+        "version map {field_id} must map Int32 field ids to Int64 versions"
+    ))
+}
+
+impl fmt::Debug for ConflictStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PreferLeft => write!(f, "PreferLeft"),
+            Self::PreferRight => write!(f, "PreferRight"),
+            Self::ErrorOnConflict => write!(f, "ErrorOnConflict"),
+            Self::ErrorOnTypeMismatch => write!(f, "ErrorOnTypeMismatch"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+            Self::LastWriterWins(source) => write!(f, "LastWriterWins({source:?})"),
+        }
+    }
+}
+
+impl ConflictStrategy {
+    /// Pick between two fields sharing an id, given the records they came from
+    fn resolve(
+        &self,
+        (left, left_record): (&RawField<'_>, &ImprintRecord),
+        (right, right_record): (&RawField<'_>, &ImprintRecord),
+    ) -> Result<Side, ImprintError> {
+        match self {
+            Self::PreferLeft => Ok(Side::Left),
+            Self::PreferRight => Ok(Side::Right),
+            Self::ErrorOnConflict => Err(ImprintError::MergeConflict(left.id)),
+            Self::ErrorOnTypeMismatch
+                if left.type_code != right.type_code
+                    && left.type_code != TypeCode::Tombstone
+                    && right.type_code != TypeCode::Tombstone =>
+            {
+                Err(ImprintError::TypeMismatch {
+                    field_id: left.id,
+                    expected: left.type_code,
+                    actual: right.type_code,
+                })
+            }
+            Self::ErrorOnTypeMismatch => Ok(Side::Left),
+            Self::Custom(resolver) => resolver(left, right),
+            Self::LastWriterWins(source) => {
+                let left_version = source.version(left_record, left.id)?;
+                let right_version = source.version(right_record, right.id)?;
+                let left = (left_version, left.type_code as u8, left.bytes);
+                let right = (right_version, right.type_code as u8, right.bytes);
+                Ok(if right > left {
+                    Side::Right
+                } else {
+                    Side::Left
+                })
+            }
+        }
+    }
+
+    /// Whether tombstones are kept in the merged record instead of deleting
+    /// the field
+    fn keeps_tombstones(&self) -> bool {
+        matches!(self, Self::LastWriterWins(_))
+    }
+
+    /// The combined version map to write to the merged record, if any
+    fn merged_versions(
+        &self,
+        records: &[&ImprintRecord],
+    ) -> Result<Option<(u16, Bytes)>, ImprintError> {
+        match self {
+            Self::LastWriterWins(source) => source.merged_versions(records),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// How a deep merge combines two arrays in the same place
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// Resolve the arrays as a conflict like any other value
+    #[default]
+    Replace,
+    /// The elements of the left array followed by those of the right
+    Concat,
+    /// The distinct elements of both arrays, in the order they first appear.
+    /// Elements are compared by their encoded bytes.
+    Union,
+}
+
+/// Options controlling [`Merge::merge_with`]
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    pub conflict: ConflictStrategy,
+    /// Whether nested values present on both sides are merged instead of
+    /// resolved as a whole: rows field by field, maps key by key, and arrays
+    /// as set by `arrays`. Values that can't be combined (such as maps or
+    /// arrays whose element types differ) are resolved as conflicts.
+    pub deep: bool,
+    /// How a deep merge combines arrays
+    pub arrays: ArrayMerge,
+}
+
+impl MergeOptions {
+    /// Options resolving conflicts with the given strategy
+    pub fn with_conflict(conflict: ConflictStrategy) -> Self {
+        Self {
+            conflict,
+            ..Default::default()
+        }
+    }
+
+    /// Merge nested values recursively, combining arrays with the given strategy
+    pub fn with_deep_merge(mut self, arrays: ArrayMerge) -> Self {
+        self.deep = true;
+        self.arrays = arrays;
+        self
+    }
+
+    /// Resolve two fields sharing an id, merging nested values when deep
+    /// merging is enabled, and return the field along with the side it
+    /// (mostly) came from
+    fn merge_fields<'a>(
+        &self,
+        (left, left_record): (MergedField<'a>, &ImprintRecord),
+        (right, right_record): (MergedField<'a>, &ImprintRecord),
+    ) -> Result<(MergedField<'a>, Side), ImprintError> {
+        let nested = match left.type_code {
+            TypeCode::Row | TypeCode::Map => true,
+            TypeCode::Array => self.arrays != ArrayMerge::Replace,
+            _ => false,
+        };
+        if self.deep && nested && left.type_code == right.type_code {
+            let (l, _) = Value::read(left.type_code, Bytes::copy_from_slice(&left.bytes))?;
+            let (r, _) = Value::read(right.type_code, Bytes::copy_from_slice(&right.bytes))?;
+            let merged = self.merge_values(left.id, (l, left_record), (r, right_record))?;
+            let mut buf = BytesMut::new();
+            merged.write(&mut buf)?;
+            let field = MergedField {
+                id: left.id,
+                type_code: left.type_code,
+                bytes: Cow::Owned(buf.to_vec()),
+            };
+            return Ok((field, Side::Left));
+        }
+
+        let side = self
+            .conflict
+            .resolve((&left.raw(), left_record), (&right.raw(), right_record))?;
+        Ok(match side {
+            Side::Left => (left, side),
+            Side::Right => (right, side),
         })
     }
+
+    /// Deep merge two decoded values nested within the given field
+    fn merge_values(
+        &self,
+        field_id: u16,
+        (left, left_record): (Value, &ImprintRecord),
+        (right, right_record): (Value, &ImprintRecord),
+    ) -> Result<Value, ImprintError> {
+        match (left, right, self.arrays) {
+            (Value::Row(left), Value::Row(right), _) => {
+                Ok(Value::Row(Box::new(left.merge_with(&right, self)?)))
+            }
+            (Value::Map(mut left), Value::Map(right), _) if same_entry_types(&left, &right) => {
+                for (key, value) in right {
+                    let value = match left.remove(&key) {
+                        Some(existing) => self.merge_values(
+                            field_id,
+                            (existing, left_record),
+                            (value, right_record),
+                        )?,
+                        None => value,
+                    };
+                    left.insert(key, value);
+                }
+                Ok(Value::Map(left))
+            }
+            (Value::Array(mut left), Value::Array(right), ArrayMerge::Concat)
+                if same_element_types(&left, &right) =>
+            {
+                left.extend(right);
+                Ok(Value::Array(left))
+            }
+            (Value::Array(left), Value::Array(right), ArrayMerge::Union)
+                if same_element_types(&left, &right) =>
+            {
+                let mut seen = HashSet::with_capacity(left.len() + right.len());
+                let mut union = Vec::with_capacity(left.len() + right.len());
+                for value in left.into_iter().chain(right) {
+                    let mut encoded = BytesMut::new();
+                    value.write(&mut encoded)?;
+                    if seen.insert(encoded) {
+                        union.push(value);
+                    }
+                }
+                Ok(Value::Array(union))
+            }
+            (left, right, _) => {
+                let mut left_bytes = BytesMut::new();
+                left.write(&mut left_bytes)?;
+                let mut right_bytes = BytesMut::new();
+                right.write(&mut right_bytes)?;
+                let raw = |value: &Value, bytes| RawField {
+                    id: field_id,
+                    type_code: value.type_code(),
+                    bytes,
+                };
+                let side = self.conflict.resolve(
+                    (&raw(&left, &left_bytes), left_record),
+                    (&raw(&right, &right_bytes), right_record),
+                )?;
+                Ok(match side {
+                    Side::Left => left,
+                    Side::Right => right,
+                })
+            }
+        }
+    }
+}
+
+/// Whether the entries of two maps can be combined into one map
+fn same_entry_types(left: &HashMap<MapKey, Value>, right: &HashMap<MapKey, Value>) -> bool {
+    match (left.iter().next(), right.iter().next()) {
+        (Some((lk, lv)), Some((rk, rv))) => {
+            lk.type_code() == rk.type_code() && lv.type_code() == rv.type_code()
+        }
+        _ => true,
+    }
+}
+
+/// Whether the elements of two arrays can be combined into one array
+fn same_element_types(left: &[Value], right: &[Value]) -> bool {
+    match (left.first(), right.first()) {
+        (Some(l), Some(r)) => l.type_code() == r.type_code(),
+        _ => true,
+    }
+}
+
+/// A field chosen by a merge, which owns its bytes when nested values were
+/// merged and borrows them from one of the merged records otherwise
+struct MergedField<'a> {
+    id: u16,
+    type_code: TypeCode,
+    bytes: Cow<'a, [u8]>,
+}
+
+impl<'a> From<RawField<'a>> for MergedField<'a> {
+    fn from(field: RawField<'a>) -> Self {
+        Self {
+            id: field.id,
+            type_code: field.type_code,
+            bytes: Cow::Borrowed(field.bytes),
+        }
+    }
+}
+
+impl MergedField<'_> {
+    fn raw(&self) -> RawField<'_> {
+        RawField {
+            id: self.id,
+            type_code: self.type_code,
+            bytes: &self.bytes,
+        }
+    }
 }
 
 pub trait Merge {
     /// Merge another record into this one, using default options.
-    /// By default, duplicate fields from the second record will be kept in the payload
-    /// but won't be accessible via the directory.
-    fn merge(&self, other: &ImprintRecord) -> Result<ImprintRecord, ImprintError>;
+    /// By default, fields present in both records are taken from this record.
+    ///
+    /// Fields resolved to a tombstone are left out of the merged record.
+    fn merge(&self, other: &ImprintRecord) -> Result<ImprintRecord, ImprintError> {
+        self.merge_with(other, &MergeOptions::default())
+    }
+
+    /// Merge another record into this one, resolving fields present in both
+    /// records according to `options`
+    fn merge_with(
+        &self,
+        other: &ImprintRecord,
+        options: &MergeOptions,
+    ) -> Result<ImprintRecord, ImprintError>;
 }
 
 impl Merge for ImprintRecord {
-    fn merge(&self, other: &ImprintRecord) -> Result<ImprintRecord, ImprintError> {
+    fn merge_with(
+        &self,
+        other: &ImprintRecord,
+        options: &MergeOptions,
+    ) -> Result<ImprintRecord, ImprintError> {
         // we just shrink the directory and payload to the exact size we need at the end of the
         // merge and allocate the largest possible sizes up front assuming that the records do
         // not have significant overlapping fields
         let mut new_directory = Vec::with_capacity(self.directory.len() + other.directory.len());
         let mut new_payload = BytesMut::with_capacity(self.payload.len() + other.payload.len());
 
+        let merged_versions = options.conflict.merged_versions(&[self, other])?;
+
         let mut self_idx = 0;
         let mut other_idx = 0;
-        let base_offset = 0u32;
         let mut current_offset = 0u32;
 
         while self_idx < self.directory.len() || other_idx < other.directory.len() {
-            let current_entry;
-            let current_payload;
-            if self_idx < self.directory.len()
-                && (other_idx >= other.directory.len()
-                    || self.directory[self_idx].id <= other.directory[other_idx].id)
-            {
-                current_entry = &self.directory[self_idx];
-                if other_idx < other.directory.len()
-                    && self.directory[self_idx].id == other.directory[other_idx].id
-                {
+            let left = self.directory.get(self_idx);
+            let right = other.directory.get(other_idx);
+            let field = match (left, right) {
+                (Some(l), Some(r)) if l.id == r.id => {
+                    let left = self.raw_field(self_idx)?.into();
+                    let right = other.raw_field(other_idx)?.into();
+                    self_idx += 1;
+                    other_idx += 1;
+                    options.merge_fields((left, self), (right, other))?.0
+                }
+                (Some(l), r) if r.is_none_or(|r| l.id < r.id) => {
+                    self_idx += 1;
+                    self.raw_field(self_idx - 1)?.into()
+                }
+                _ => {
                     other_idx += 1;
+                    other.raw_field(other_idx - 1)?.into()
                 }
-                current_payload = self.get_raw_bytes(current_entry.id).unwrap();
-                self_idx += 1;
-            } else {
-                current_entry = &other.directory[other_idx];
-                current_payload = other.get_raw_bytes(current_entry.id).unwrap();
-                other_idx += 1;
             };
 
+            let field = versioned(field, &merged_versions);
+
+            // tombstones delete the field from the merged record
+            if field.type_code == TypeCode::Tombstone && !options.conflict.keeps_tombstones() {
+                continue;
+            }
+
             // Add adjusted directory entry
-            let new_entry = DirectoryEntry {
-                id: current_entry.id,
-                type_code: current_entry.type_code,
-                offset: base_offset + current_offset,
-            };
-            new_directory.push(new_entry);
+            new_directory.push(DirectoryEntry {
+                id: field.id,
+                type_code: field.type_code,
+                offset: current_offset,
+            });
 
             // Copy corresponding payload
-            let offset_delta = current_payload.len() as u32;
-            new_payload.extend_from_slice(current_payload.as_ref());
-            current_offset += offset_delta;
+            new_payload.extend_from_slice(&field.bytes);
+            current_offset += field.bytes.len() as u32;
         }
 
         // Shrink allocations to fit actual data
         new_directory.shrink_to_fit();
 
-        Ok(ImprintRecord {
-            header: Header {
-                flags: self.header.flags,
-                schema_id: self.header.schema_id,
-                payload_size: new_payload.len() as u32,
-            },
-            directory: new_directory,
-            payload: new_payload.freeze(),
+        Ok(ImprintRecord::from_parts(
+            self.header.flags,
+            self.header.schema_id.fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+}
+
+impl ImprintRecord {
+    /// Merge any number of records in a single pass, using default options.
+    /// Equivalent to merging the records left to right with [`Merge::merge`],
+    /// but each surviving field is copied exactly once.
+    pub fn merge_all<'a>(
+        records: impl IntoIterator<Item = &'a ImprintRecord>,
+    ) -> Result<ImprintRecord, ImprintError> {
+        Self::merge_all_with(records, &MergeOptions::default())
+    }
+
+    /// Merge any number of records in a single pass. Equivalent to merging the
+    /// records left to right with [`Merge::merge_with`], but each surviving
+    /// field is copied exactly once.
+    pub fn merge_all_with<'a>(
+        records: impl IntoIterator<Item = &'a ImprintRecord>,
+        options: &MergeOptions,
+    ) -> Result<ImprintRecord, ImprintError> {
+        let records: Vec<_> = records.into_iter().collect();
+        let Some(first) = records.first() else {
+            return Ok(ImprintRecord::from_parts(
+                Flags::new(0),
+                0,
+                Vec::new(),
+                Bytes::new(),
+            ));
+        };
+        // chaining zero merges leaves the first record, tombstones and all
+        if records.len() == 1 {
+            return Ok((*first).clone());
+        }
+
+        // as with the two-way merge, allocate for the case where no fields overlap
+        let mut new_directory = Vec::with_capacity(records.iter().map(|r| r.directory.len()).sum());
+        let mut new_payload =
+            BytesMut::with_capacity(records.iter().map(|r| r.payload.len()).sum());
+
+        // k-way merge over the directories: the heap holds the next field id of
+        // each record, ordered by record index within an id so that conflicts
+        // are folded in the same order as chained merges
+        let mut cursors = vec![0; records.len()];
+        let mut heap: BinaryHeap<_> = records
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, r)| r.directory.first().map(|e| Reverse((e.id, idx))))
+            .collect();
+        let mut current_offset = 0u32;
+        let merged_versions = options.conflict.merged_versions(&records)?;
+        let keeps_tombstones = options.conflict.keeps_tombstones();
+
+        while let Some(Reverse((id, idx))) = heap.pop() {
+            let mut winner = idx;
+            let mut field: MergedField<'_> = records[idx].raw_field(cursors[idx])?.into();
+            advance(&records, &mut cursors, &mut heap, idx);
+
+            while let Some(&Reverse((next_id, next_idx))) = heap.peek()
+                && next_id == id
+            {
+                heap.pop();
+                let other = records[next_idx].raw_field(cursors[next_idx])?;
+                advance(&records, &mut cursors, &mut heap, next_idx);
+                // a chained merge starts from the first record as is, but drops
+                // tombstones from the result of every merge after that, so only
+                // a tombstone from the first record conflicts with the second
+                // record's field. Any other tombstone would already be gone.
+                if field.type_code == TypeCode::Tombstone && !keeps_tombstones && next_idx != 1 {
+                    winner = next_idx;
+                    field = other.into();
+                    continue;
+                }
+                let side;
+                (field, side) = options
+                    .merge_fields((field, records[winner]), (other.into(), records[next_idx]))?;
+                if side == Side::Right {
+                    winner = next_idx;
+                }
+            }
+
+            let field = versioned(field, &merged_versions);
+            if field.type_code == TypeCode::Tombstone && !keeps_tombstones {
+                continue;
+            }
+
+            new_directory.push(DirectoryEntry {
+                id: field.id,
+                type_code: field.type_code,
+                offset: current_offset,
+            });
+            new_payload.extend_from_slice(&field.bytes);
+            current_offset += field.bytes.len() as u32;
+        }
+
+        new_directory.shrink_to_fit();
+
+        Ok(ImprintRecord::from_parts(
+            first.header.flags,
+            first.header.schema_id.fieldspace_id,
+            new_directory,
+            new_payload.freeze(),
+        ))
+    }
+
+    /// The raw field at the given directory index
+    pub(crate) fn raw_field(&self, idx: usize) -> Result<RawField<'_>, ImprintError> {
+        let entry = &self.directory[idx];
+        Ok(RawField {
+            id: entry.id,
+            type_code: entry.type_code,
+            bytes: &self.payload[self.field_range(idx)?],
         })
     }
 }
 
+/// Replace the version map field with the combined versions of all records
+fn versioned<'a>(
+    field: MergedField<'a>,
+    merged_versions: &'a Option<(u16, Bytes)>,
+) -> MergedField<'a> {
+    match merged_versions {
+        Some((versions_id, bytes)) if field.id == *versions_id => MergedField {
+            id: field.id,
+            type_code: TypeCode::Map,
+            bytes: Cow::Borrowed(bytes),
+        },
+        _ => field,
+    }
+}
+
+/// Move a record's cursor past its current field, queueing its next field id
+fn advance(
+    records: &[&ImprintRecord],
+    cursors: &mut [usize],
+    heap: &mut BinaryHeap<Reverse<(u16, usize)>>,
+    idx: usize,
+) {
+    cursors[idx] += 1;
+    if let Some(entry) = records[idx].directory.get(cursors[idx]) {
+        heap.push(Reverse((entry.id, idx)));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ImprintWriter;
+    use crate::test_support::{create_record, create_update};
+    use crate::{ImprintWriter, MapKey, SchemaId, Value};
 
     fn create_test_record() -> ImprintRecord {
         let mut writer = ImprintWriter::new(SchemaId {
@@ -229,7 +1034,7 @@ mod tests {
 
         // Then all fields should be present with matching values
         assert_eq!(projected.directory.len(), record.directory.len());
-        for entry in &record.directory {
+        for entry in record.directory.iter() {
             assert_eq!(
                 projected.get_value(entry.id).unwrap(),
                 record.get_value(entry.id).unwrap(),
@@ -446,28 +1251,801 @@ mod tests {
     }
 
     #[test]
-    fn should_preserve_schema_id_from_first_record() {
-        // Given two records with different schema IDs
-        let schema1 = SchemaId {
+    fn should_preserve_fieldspace_from_first_record() {
+        // Given two records from different fieldspaces
+        let mut writer1 = ImprintWriter::new(SchemaId {
             fieldspace_id: 1,
-            schema_hash: 0xdeadbeef,
-        };
-        let mut writer1 = ImprintWriter::new(schema1).unwrap();
+            schema_hash: 0,
+        })
+        .unwrap();
         writer1.add_field(1, 42.into()).unwrap();
         let record1 = writer1.build().unwrap();
 
-        let schema2 = SchemaId {
-            fieldspace_id: 1,
-            schema_hash: 0xcafebabe,
-        };
-        let mut writer2 = ImprintWriter::new(schema2).unwrap();
+        let mut writer2 = ImprintWriter::new(SchemaId {
+            fieldspace_id: 2,
+            schema_hash: 0,
+        })
+        .unwrap();
         writer2.add_field(2, true.into()).unwrap();
         let record2 = writer2.build().unwrap();
 
         // When merging the records
         let merged = record1.merge(&record2).unwrap();
 
-        // Then schema ID from first record should be preserved
-        assert_eq!(merged.header.schema_id, schema1);
+        // Then the fieldspace from the first record should be preserved
+        assert_eq!(merged.header.schema_id.fieldspace_id, 1);
+    }
+
+    #[test]
+    fn should_compute_schema_hash_of_projection() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // And a record written with only a subset of those fields
+        let mut writer = ImprintWriter::new(record.header.schema_id).unwrap();
+        writer.add_field(1, 7.into()).unwrap();
+        writer.add_field(5, false.into()).unwrap();
+        let expected = writer.build().unwrap();
+
+        // When projecting the same subset of fields
+        let projected = record.project(&[1, 5]).unwrap();
+
+        // Then both should share a schema id that differs from the original
+        assert_eq!(projected.header.schema_id, expected.header.schema_id);
+        assert_ne!(projected.header.schema_id, record.header.schema_id);
+    }
+
+    #[test]
+    fn should_compute_schema_hash_of_merge() {
+        // Given two records with overlapping fields
+        let (record1, record2) = create_overlapping_records();
+
+        // And a record written with the union of their fields
+        let mut writer = ImprintWriter::new(record1.header.schema_id).unwrap();
+        writer.add_field(1, false.into()).unwrap();
+        writer.add_field(2, "other".into()).unwrap();
+        writer.add_field(3, 0.into()).unwrap();
+        let expected = writer.build().unwrap();
+
+        // When merging the records
+        let merged = record1.merge(&record2).unwrap();
+
+        // Then the merged schema id should match the written one
+        assert_eq!(merged.header.schema_id, expected.header.schema_id);
+    }
+
+    #[test]
+    fn should_prefer_right_when_configured() {
+        let (record1, record2) = create_overlapping_records();
+
+        // When merging with the right record taking precedence
+        let options = MergeOptions::with_conflict(ConflictStrategy::PreferRight);
+        let merged = record1.merge_with(&record2, &options).unwrap();
+
+        // Then the duplicate field should come from the right record
+        assert_eq!(merged.directory.len(), 3);
+        assert_eq!(merged.get_value(2).unwrap(), Some("second".into()));
+        assert_eq!(merged.get_value(3).unwrap(), Some(42.into()));
+        assert_eq!(
+            merged.payload.len(),
+            1 + (1 + "second".len()) + 4,
+            "discarded value should not be copied"
+        );
+    }
+
+    #[test]
+    fn should_error_on_conflict_when_configured() {
+        let (record1, record2) = create_overlapping_records();
+
+        // When merging records that share a field id
+        let options = MergeOptions::with_conflict(ConflictStrategy::ErrorOnConflict);
+        let result = record1.merge_with(&record2, &options);
+
+        // Then the shared field should be reported
+        assert!(matches!(result, Err(ImprintError::MergeConflict(2))));
+    }
+
+    #[test]
+    fn should_only_error_on_type_mismatch_when_configured() {
+        let (record1, record2) = create_overlapping_records();
+        let options = MergeOptions::with_conflict(ConflictStrategy::ErrorOnTypeMismatch);
+
+        // When the shared field has the same type in both records
+        let merged = record1.merge_with(&record2, &options).unwrap();
+
+        // Then the left field should be kept
+        assert_eq!(merged.get_value(2).unwrap(), Some("first".into()));
+
+        // But when the shared field has different types
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(3, 42i64.into()).unwrap();
+        let mismatched = writer.build().unwrap();
+        let result = record1.merge_with(&mismatched, &options);
+
+        // Then the merge should fail naming both types
+        assert!(matches!(
+            result,
+            Err(ImprintError::TypeMismatch {
+                field_id: 3,
+                expected: TypeCode::Int32,
+                actual: TypeCode::Int64,
+            })
+        ));
+    }
+
+    #[test]
+    fn should_resolve_conflicts_with_callback() {
+        let (record1, record2) = create_overlapping_records();
+
+        // When merging with a callback that keeps the longer encoding
+        let options = MergeOptions::with_conflict(ConflictStrategy::Custom(Arc::new(
+            |left: &RawField<'_>, right: &RawField<'_>| {
+                assert_eq!(left.id, right.id);
+                Ok(if right.bytes.len() > left.bytes.len() {
+                    Side::Right
+                } else {
+                    Side::Left
+                })
+            },
+        )));
+        let merged = record1.merge_with(&record2, &options).unwrap();
+
+        // Then the callback's choice should be kept
+        assert_eq!(merged.get_value(2).unwrap(), Some("second".into()));
+
+        // And errors from the callback should be propagated
+        let failing = MergeOptions::with_conflict(ConflictStrategy::Custom(Arc::new(
+            |left: &RawField<'_>, _: &RawField<'_>| Err(ImprintError::MergeConflict(left.id)),
+        )));
+        assert!(matches!(
+            record1.merge_with(&record2, &failing),
+            Err(ImprintError::MergeConflict(2))
+        ));
+    }
+
+    #[test]
+    fn should_merge_all_like_chained_merges() {
+        // Given several partial records with overlapping fields
+        let records = [
+            create_record(&[(2, "a".into()), (5, 1.into())]),
+            create_record(&[(1, true.into()), (2, "b".into())]),
+            create_record(&[(2, "c".into()), (5, 2.into()), (9, 3i64.into())]),
+            create_record(&[]),
+        ];
+
+        for strategy in [ConflictStrategy::PreferLeft, ConflictStrategy::PreferRight] {
+            let options = MergeOptions::with_conflict(strategy);
+
+            // When merging them all at once
+            let merged = ImprintRecord::merge_all_with(&records, &options).unwrap();
+
+            // Then the result should match chaining two-way merges
+            let mut chained = records[0].clone();
+            for record in &records[1..] {
+                chained = chained.merge_with(record, &options).unwrap();
+            }
+            assert_eq!(merged, chained);
+        }
+    }
+
+    #[test]
+    fn should_fold_conflicts_in_record_order() {
+        // Given three records sharing a field id
+        let records = [
+            create_record(&[(1, "x".into())]),
+            create_record(&[(1, "yy".into())]),
+            create_record(&[(1, "z".into())]),
+        ];
+
+        // When a callback records the order it sees conflicts in
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let options = MergeOptions::with_conflict(ConflictStrategy::Custom(Arc::new(
+            move |left: &RawField<'_>, right: &RawField<'_>| {
+                log.lock()
+                    .unwrap()
+                    .push((left.bytes.to_vec(), right.bytes.to_vec()));
+                Ok(Side::Right)
+            },
+        )));
+        let merged = ImprintRecord::merge_all_with(&records, &options).unwrap();
+
+        // Then the winner of each step should meet the next record
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (b"\x01x".to_vec(), b"\x02yy".to_vec()),
+                (b"\x02yy".to_vec(), b"\x01z".to_vec()),
+            ]
+        );
+        assert_eq!(merged.get_value(1).unwrap(), Some("z".into()));
+    }
+
+    #[test]
+    fn should_merge_all_of_nothing() {
+        // When merging an empty set of records
+        let merged = ImprintRecord::merge_all(&[]).unwrap();
+
+        // Then the result should be an empty record
+        assert_eq!(merged.field_count(), 0);
+        assert!(merged.payload.is_empty());
+
+        // And merging a single record should reproduce it
+        let record = create_test_record();
+        assert_eq!(ImprintRecord::merge_all([&record]).unwrap(), record);
+    }
+
+    #[test]
+    fn should_project_past_missing_ids() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When projecting ids interleaved with ones that aren't present
+        let projected = record.project(&[7, 2, 3, 6, 99]).unwrap();
+
+        // Then only the present fields should be kept
+        assert_eq!(projected, record.drop_fields(&[1, 5]).unwrap());
+    }
+
+    #[test]
+    fn should_drop_listed_fields() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When dropping some fields, including ones that aren't present
+        let dropped = record.drop_fields(&[3, 7, 99, 3]).unwrap();
+
+        // Then every other field should be kept
+        assert_eq!(dropped.directory.len(), 2);
+        assert_eq!(dropped.get_value(1).unwrap(), Some(42.into()));
+        assert_eq!(dropped.get_value(5).unwrap(), Some(true.into()));
+        assert_eq!(dropped.get_value(3).unwrap(), None);
+        assert_eq!(dropped.get_value(7).unwrap(), None);
+
+        // And it should match projecting the survivors
+        assert_eq!(dropped, record.project(&[1, 5]).unwrap());
+    }
+
+    #[test]
+    fn should_keep_everything_when_dropping_nothing() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When dropping no fields
+        let dropped = record.drop_fields(&[]).unwrap();
+
+        // Then the record should be unchanged
+        assert_eq!(dropped, record);
+    }
+
+    #[test]
+    fn should_remap_field_ids_into_target_fieldspace() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When remapping a subset of fields into a different order
+        let remapped = record
+            .project_remap(&[(1, 20), (3, 10), (7, 30), (99, 40)], 2)
+            .unwrap();
+
+        // Then the values should be under their new ids in sorted order
+        let ids: Vec<u16> = remapped.directory.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![10, 20, 30]);
+        assert_eq!(remapped.get_value(10).unwrap(), Some("hello".into()));
+        assert_eq!(remapped.get_value(20).unwrap(), Some(42.into()));
+        assert_eq!(remapped.get_value(30).unwrap(), Some(vec![1, 2, 3].into()));
+        assert_eq!(remapped.get_raw_bytes(10), record.get_raw_bytes(3));
+
+        // And the schema id should be that of the target fieldspace
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 2,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(10, "other".into()).unwrap();
+        writer.add_field(20, 0.into()).unwrap();
+        writer.add_field(30, vec![0].into()).unwrap();
+        assert_eq!(
+            remapped.header.schema_id,
+            writer.build().unwrap().header.schema_id
+        );
+    }
+
+    #[test]
+    fn should_reject_remapping_two_fields_to_one_id() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When two fields are mapped to the same target id
+        let result = record.project_remap(&[(1, 10), (5, 10)], 2);
+
+        // Then the remap should fail naming the target id
+        assert!(matches!(result, Err(ImprintError::DuplicateFieldId(10))));
+    }
+
+    #[test]
+    fn should_project_into_nested_rows() {
+        // Given a record with a wide nested row, itself nesting a row
+        let record = create_record(&[
+            (1, 42.into()),
+            (3, "hello".into()),
+            (
+                7,
+                row(&[
+                    (1, "a".into()),
+                    (2, "b".repeat(100).into()),
+                    (4, row(&[(1, 1.into()), (2, 2.into())])),
+                    (5, true.into()),
+                ]),
+            ),
+        ]);
+
+        // When projecting one top-level field whole and some nested sub-fields
+        let spec = ProjectionSpec::new().field(3).nested(
+            7,
+            ProjectionSpec::new()
+                .field(1)
+                .nested(4, [2].into_iter().collect()),
+        );
+        let projected = record.project_nested(&spec).unwrap();
+
+        // Then it should match a record written with only those fields
+        assert_eq!(
+            projected,
+            create_record(&[
+                (3, "hello".into()),
+                (7, row(&[(1, "a".into()), (4, row(&[(2, 2.into())]))])),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_project_nested_like_flat_projection_without_sub_specs() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When projecting with a spec that names only whole fields
+        let projected = record
+            .project_nested(&[1, 7, 99].into_iter().collect())
+            .unwrap();
+
+        // Then it should match a flat projection
+        assert_eq!(projected, record.project(&[1, 7]).unwrap());
+    }
+
+    #[test]
+    fn should_reject_nested_spec_on_non_row_field() {
+        // Given a record with multiple fields
+        let record = create_test_record();
+
+        // When projecting sub-fields out of a string
+        let result = record.project_nested(&ProjectionSpec::new().nested(3, ProjectionSpec::new()));
+
+        // Then the projection should fail naming the field
+        assert!(matches!(
+            result,
+            Err(ImprintError::TypeMismatch {
+                field_id: 3,
+                expected: TypeCode::Row,
+                actual: TypeCode::String
+            })
+        ));
+    }
+
+    #[test]
+    fn should_remove_fields_deleted_by_tombstones() {
+        // Given a record and a partial update unsetting some of its fields
+        let record = create_test_record();
+        let update = create_update(&[(1, 43.into())], &[3, 7, 9]);
+
+        // When applying the update
+        let options = MergeOptions::with_conflict(ConflictStrategy::PreferRight);
+        let merged = record.merge_with(&update, &options).unwrap();
+
+        // Then the tombstoned fields should be gone
+        assert_eq!(merged, create_record(&[(1, 43.into()), (5, true.into())]));
+
+        // But a tombstone that loses the conflict should not delete anything
+        let merged = record.merge(&update).unwrap();
+        assert_eq!(merged, record);
+    }
+
+    #[test]
+    fn should_not_treat_tombstones_as_type_mismatches() {
+        // Given a record and an update unsetting one of its fields
+        let record = create_test_record();
+        let update = create_update(&[], &[3]);
+
+        // When merging with type checking
+        let options = MergeOptions::with_conflict(ConflictStrategy::ErrorOnTypeMismatch);
+        let merged = update.merge_with(&record, &options).unwrap();
+
+        // Then the tombstone should win as the left side
+        assert!(!merged.contains(3));
+        assert_eq!(merged.field_count(), 3);
+    }
+
+    #[test]
+    fn should_honor_tombstones_in_merge_all() {
+        // Given a chain of updates that unsets a field and sets it again
+        let records = [
+            create_record(&[(1, 1.into()), (2, "a".into())]),
+            create_update(&[], &[2]),
+            create_update(&[(2, "b".into())], &[1]),
+            create_update(&[(3, 3.into())], &[]),
+        ];
+
+        for strategy in [ConflictStrategy::PreferLeft, ConflictStrategy::PreferRight] {
+            let options = MergeOptions::with_conflict(strategy);
+
+            // When merging them all at once
+            let merged = ImprintRecord::merge_all_with(&records, &options).unwrap();
+
+            // Then the result should match chaining two-way merges
+            let mut chained = records[0].clone();
+            for record in &records[1..] {
+                chained = chained.merge_with(record, &options).unwrap();
+            }
+            assert_eq!(merged, chained);
+        }
+
+        // And the last update should win when preferring the right side
+        let options = MergeOptions::with_conflict(ConflictStrategy::PreferRight);
+        let merged = ImprintRecord::merge_all_with(&records, &options).unwrap();
+        assert_eq!(merged, create_record(&[(2, "b".into()), (3, 3.into())]));
+    }
+
+    #[test]
+    fn should_resolve_tombstones_in_first_record_like_chained_merges() {
+        // Given a first record unsetting a field that later records set
+        let records = [
+            create_update(&[(1, 1.into())], &[2, 3]),
+            create_record(&[(2, "a".into())]),
+            create_record(&[(2, "b".into()), (3, "c".into())]),
+        ];
+        let resolver: Arc<ConflictResolver> = Arc::new(|_, _| Ok(Side::Right));
+        let strategies = [
+            ConflictStrategy::PreferLeft,
+            ConflictStrategy::PreferRight,
+            ConflictStrategy::ErrorOnConflict,
+            ConflictStrategy::ErrorOnTypeMismatch,
+            ConflictStrategy::Custom(resolver),
+        ];
+
+        for strategy in strategies {
+            let options = MergeOptions::with_conflict(strategy);
+
+            // When merging them all at once and by chaining two-way merges
+            let merged = ImprintRecord::merge_all_with(&records, &options);
+            let chained = records[1..]
+                .iter()
+                .try_fold(records[0].clone(), |acc, r| acc.merge_with(r, &options));
+
+            // Then both should agree, including on conflicts
+            match (merged, chained) {
+                (Ok(merged), Ok(chained)) => assert_eq!(merged, chained, "{options:?}"),
+                (Err(ImprintError::MergeConflict(a)), Err(ImprintError::MergeConflict(b))) => {
+                    assert_eq!(a, b)
+                }
+                (merged, chained) => panic!("{options:?}: {merged:?} != {chained:?}"),
+            }
+        }
+
+        // And a single record should be returned as is
+        let merged = ImprintRecord::merge_all([&records[0]]).unwrap();
+        assert_eq!(merged, records[0]);
+    }
+
+    /// Every ordering of three records
+    fn permutations<T: Clone>(items: &[T; 3]) -> Vec<[T; 3]> {
+        [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ]
+        .iter()
+        .map(|order| order.map(|i| items[i].clone()))
+        .collect()
+    }
+
+    fn assert_order_independent(
+        records: &[ImprintRecord; 3],
+        options: &MergeOptions,
+        chained: bool,
+    ) -> ImprintRecord {
+        let expected = ImprintRecord::merge_all_with(records, options).unwrap();
+        for [a, b, c] in permutations(records) {
+            assert_eq!(
+                ImprintRecord::merge_all_with([&a, &b, &c], options).unwrap(),
+                expected
+            );
+            if !chained {
+                continue;
+            }
+            let chained = a.merge_with(&b, options).unwrap();
+            assert_eq!(chained.merge_with(&c, options).unwrap(), expected);
+            let chained = b.merge_with(&c, options).unwrap();
+            assert_eq!(a.merge_with(&chained, options).unwrap(), expected);
+        }
+        expected
+    }
+
+    #[test]
+    fn should_keep_newest_fields_by_record_timestamp() {
+        // Given partial rows timestamped in field 0
+        let records = [
+            create_record(&[(0, 10i64.into()), (1, "a".into()), (2, 1.into())]),
+            create_record(&[(0, 30i64.into()), (1, "c".into())]),
+            create_record(&[(0, 20i64.into()), (2, 2.into()), (3, true.into())]),
+        ];
+
+        // When merging them in any order
+        let options = MergeOptions::with_conflict(ConflictStrategy::LastWriterWins(
+            VersionSource::RecordTimestamp(0),
+        ));
+        let merged = assert_order_independent(&records, &options, false);
+
+        // Then each field should come from the newest record holding it
+        assert_eq!(
+            merged,
+            create_record(&[
+                (0, 30i64.into()),
+                (1, "c".into()),
+                (2, 2.into()),
+                (3, true.into())
+            ])
+        );
+    }
+
+    fn versions(entries: &[(i32, i64)]) -> Value {
+        Value::Map(
+            entries
+                .iter()
+                .map(|&(id, version)| (MapKey::Int32(id), Value::Int64(version)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn should_keep_newest_fields_by_field_versions() {
+        // Given partial rows with per-field versions in field 100
+        let records = [
+            create_record(&[
+                (1, "a1".into()),
+                (2, "b1".into()),
+                (100, versions(&[(1, 1), (2, 5)])),
+            ]),
+            create_record(&[
+                (1, "a2".into()),
+                (2, "b2".into()),
+                (100, versions(&[(1, 3), (2, 2)])),
+            ]),
+            create_record(&[(1, "a3".into()), (100, versions(&[(1, 2)]))]),
+        ];
+
+        // When merging them in any order
+        let options = MergeOptions::with_conflict(ConflictStrategy::LastWriterWins(
+            VersionSource::FieldVersions(100),
+        ));
+        let merged = assert_order_independent(&records, &options, true);
+
+        // Then each field should have its newest value
+        assert_eq!(merged.get_value(1).unwrap(), Some("a2".into()));
+        assert_eq!(merged.get_value(2).unwrap(), Some("b1".into()));
+
+        // And the versions should be combined
+        assert_eq!(
+            merged.get_value(100).unwrap(),
+            Some(versions(&[(1, 3), (2, 5)]))
+        );
+    }
+
+    #[test]
+    fn should_keep_newer_tombstones_with_last_writer_wins() {
+        // Given a write, a newer deletion and a newest write of another field
+        let records = [
+            create_record(&[(0, 1i64.into()), (1, "old".into())]),
+            create_update(&[(0, 2i64.into())], &[1]),
+            create_record(&[(0, 3i64.into()), (2, 2.into())]),
+        ];
+
+        // When merging them in any order
+        let options = MergeOptions::with_conflict(ConflictStrategy::LastWriterWins(
+            VersionSource::RecordTimestamp(0),
+        ));
+        let merged = assert_order_independent(&records, &options, false);
+
+        // Then the deleted field should stay deleted
+        assert!(merged.is_tombstone(1));
+        assert_eq!(merged.get_value(1).unwrap(), None);
+        assert_eq!(merged.get_value(2).unwrap(), Some(2.into()));
+    }
+
+    #[test]
+    fn should_break_version_ties_deterministically() {
+        // Given rows with the same timestamp but different values
+        let records = [
+            create_record(&[(0, 1i64.into()), (1, "x".into())]),
+            create_record(&[(0, 1i64.into()), (1, "y".into())]),
+            create_record(&[(0, 1i64.into()), (1, 7.into())]),
+        ];
+
+        // When merging them in any order
+        let options = MergeOptions::with_conflict(ConflictStrategy::LastWriterWins(
+            VersionSource::RecordTimestamp(0),
+        ));
+        let merged = assert_order_independent(&records, &options, true);
+
+        // Then the same value should always win
+        assert_eq!(merged.get_value(1).unwrap(), Some("y".into()));
+    }
+
+    fn row(fields: &[(u16, Value)]) -> Value {
+        create_record(fields).into()
+    }
+
+    #[test]
+    fn should_deep_merge_nested_rows() {
+        // Given two documents holding different parts of a nested address
+        let left = create_record(&[
+            (1, "alice".into()),
+            (
+                2,
+                row(&[(1, "main st".into()), (3, row(&[(1, "us".into())]))]),
+            ),
+        ]);
+        let right = create_record(&[
+            (
+                2,
+                row(&[(2, "springfield".into()), (3, row(&[(2, 12345.into())]))]),
+            ),
+            (4, true.into()),
+        ]);
+
+        // When deep merging them
+        let options = MergeOptions::default().with_deep_merge(ArrayMerge::Replace);
+        let merged = left.merge_with(&right, &options).unwrap();
+
+        // Then the nested rows should be merged field by field
+        assert_eq!(
+            merged,
+            create_record(&[
+                (1, "alice".into()),
+                (
+                    2,
+                    row(&[
+                        (1, "main st".into()),
+                        (2, "springfield".into()),
+                        (3, row(&[(1, "us".into()), (2, 12345.into())]))
+                    ])
+                ),
+                (4, true.into()),
+            ])
+        );
+
+        // And a shallow merge should keep the left row whole
+        let shallow = left.merge(&right).unwrap();
+        assert_eq!(shallow.get_value(2).unwrap(), left.get_value(2).unwrap());
+    }
+
+    #[test]
+    fn should_deep_merge_maps_by_key() {
+        let tags = |entries: &[(&str, Value)]| {
+            Value::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (MapKey::String(k.to_string()), v.clone()))
+                    .collect(),
+            )
+        };
+
+        // Given two maps with overlapping keys, one holding nested rows
+        let left = create_record(&[(1, tags(&[("a", row(&[(1, 1.into())])), ("b", row(&[]))]))]);
+        let right = create_record(&[(1, tags(&[("a", row(&[(2, 2.into())])), ("c", row(&[]))]))]);
+
+        // When deep merging them
+        let options = MergeOptions::default().with_deep_merge(ArrayMerge::Replace);
+        let merged = left.merge_with(&right, &options).unwrap();
+
+        // Then keys from both sides should be kept and shared keys merged
+        assert_eq!(
+            merged.get_value(1).unwrap(),
+            Some(tags(&[
+                ("a", row(&[(1, 1.into()), (2, 2.into())])),
+                ("b", row(&[])),
+                ("c", row(&[])),
+            ]))
+        );
+    }
+
+    #[test]
+    fn should_combine_arrays_with_configured_strategy() {
+        // Given two records with overlapping arrays
+        let left = create_record(&[(1, vec![1, 2, 2].into())]);
+        let right = create_record(&[(1, vec![2, 3].into())]);
+
+        let merge = |arrays| {
+            let options =
+                MergeOptions::with_conflict(ConflictStrategy::PreferRight).with_deep_merge(arrays);
+            left.merge_with(&right, &options)
+                .unwrap()
+                .get_value(1)
+                .unwrap()
+        };
+
+        // Then each strategy should combine them as configured
+        assert_eq!(merge(ArrayMerge::Replace), Some(vec![2, 3].into()));
+        assert_eq!(merge(ArrayMerge::Concat), Some(vec![1, 2, 2, 2, 3].into()));
+        assert_eq!(merge(ArrayMerge::Union), Some(vec![1, 2, 3].into()));
+
+        // And a union should dedupe elements that never compare equal
+        let left = create_record(&[(1, Value::Array(vec![f64::NAN.into(), 1.0f64.into()]))]);
+        let right = create_record(&[(1, Value::Array(vec![f64::NAN.into()]))]);
+        let options = MergeOptions::default().with_deep_merge(ArrayMerge::Union);
+        let merged = left.merge_with(&right, &options).unwrap();
+        assert_eq!(merged, left);
+    }
+
+    #[test]
+    fn should_resolve_uncombinable_nested_values_as_conflicts() {
+        // Given arrays and maps whose element types differ
+        let left = create_record(&[
+            (1, vec![1, 2].into()),
+            (2, Value::Map([(MapKey::Int32(1), Value::Int32(1))].into())),
+        ]);
+        let right = create_record(&[
+            (1, Value::Array(vec![Value::Int64(3)])),
+            (2, Value::Map([(MapKey::Int32(1), Value::Int64(1))].into())),
+        ]);
+
+        // When deep merging with concatenated arrays
+        let options = MergeOptions::with_conflict(ConflictStrategy::PreferRight)
+            .with_deep_merge(ArrayMerge::Concat);
+        let merged = left.merge_with(&right, &options).unwrap();
+
+        // Then the right values should win whole
+        assert_eq!(merged, right);
+
+        // And conflicts should fail when configured to
+        let options = MergeOptions::with_conflict(ConflictStrategy::ErrorOnConflict)
+            .with_deep_merge(ArrayMerge::Concat);
+        let result = left.merge_with(&right, &options);
+        assert!(matches!(result, Err(ImprintError::MergeConflict(1))));
+    }
+
+    #[test]
+    fn should_deep_merge_all_like_chained_merges() {
+        // Given several partial documents sharing a nested row and array
+        let records = [
+            create_record(&[(1, row(&[(1, "a".into())])), (2, vec![1].into())]),
+            create_record(&[(1, row(&[(2, "b".into())])), (2, vec![2].into())]),
+            create_record(&[(1, row(&[(1, "c".into())])), (2, vec![1, 3].into())]),
+        ];
+
+        // When deep merging them all at once
+        let options = MergeOptions::default().with_deep_merge(ArrayMerge::Union);
+        let merged = ImprintRecord::merge_all_with(&records, &options).unwrap();
+
+        // Then the result should match chaining two-way merges
+        let chained = records[0]
+            .merge_with(&records[1], &options)
+            .unwrap()
+            .merge_with(&records[2], &options)
+            .unwrap();
+        assert_eq!(merged, chained);
+        assert_eq!(
+            merged,
+            create_record(&[
+                (1, row(&[(1, "a".into()), (2, "b".into())])),
+                (2, vec![1, 2, 3].into())
+            ])
+        );
     }
 }
@@ -0,0 +1,165 @@
+use crate::{
+    error::ImprintError,
+    types::{ImprintRecord, MapKey, TypeCode},
+    value_ref::ValueRef,
+};
+
+/// One step of a path into nested values
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    /// A field of a row
+    Field(u16),
+    /// An element of an array
+    Index(usize),
+    /// The value for a key of a map
+    Key(MapKey),
+}
+
+impl ImprintRecord {
+    /// Get a borrowed view of the value at a path of fields, array indices and
+    /// map keys, such as `[Field(3), Field(7), Index(2), Key("zip".into())]`.
+    ///
+    /// Nested rows are walked through their directories and array and map
+    /// elements are skipped over without being decoded, so only the value at
+    /// the end of the path is read. Returns `None` if any field, index or key
+    /// along the path is missing, or an [`ImprintError::InvalidPath`] if a
+    /// segment is applied to a value of the wrong type.
+    pub fn get_path(&self, path: &[PathSegment]) -> Result<Option<ValueRef<'_>>, ImprintError> {
+        // the record itself isn't a value, so an empty path leads nowhere
+        let Some((first, rest)) = path.split_first() else {
+            return Ok(None);
+        };
+        let PathSegment::Field(field_id) = first else {
+            return Err(ImprintError::InvalidPath {
+                segment: 0,
+                actual: TypeCode::Row,
+            });
+        };
+
+        let mut current = match self.get_value_ref(*field_id)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        for (idx, segment) in rest.iter().enumerate() {
+            let next = match (segment, &current) {
+                (PathSegment::Field(id), ValueRef::Row(row)) => row.get_value_ref(*id)?,
+                (PathSegment::Index(index), ValueRef::Array(array)) => array.get(*index)?,
+                (PathSegment::Key(key), ValueRef::Map(map)) => map.get(key)?,
+                _ => {
+                    return Err(ImprintError::InvalidPath {
+                        segment: idx + 1,
+                        actual: current.type_code(),
+                    });
+                }
+            };
+            match next {
+                Some(value) => current = value,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::PathSegment::{Field, Index, Key};
+    use super::*;
+    use crate::Value;
+    use crate::test_support::create_record;
+
+    fn create_order() -> ImprintRecord {
+        let address = |zip: &str| {
+            let map: HashMap<_, _> = [
+                (MapKey::from("street"), Value::from("1 Main St")),
+                (MapKey::from("zip"), Value::from(zip)),
+            ]
+            .into();
+            Value::Map(map)
+        };
+        let customer = create_record(&[
+            (1, "Ada".into()),
+            (
+                7,
+                Value::Array(vec![address("10001"), address("94105"), address("60601")]),
+            ),
+        ]);
+        create_record(&[(1, 1234i64.into()), (3, customer.into())])
+    }
+
+    #[test]
+    fn should_walk_rows_arrays_and_maps() {
+        // Given an order nesting a customer with a list of addresses
+        let order = create_order();
+
+        // When reading a leaf through every kind of segment
+        let zip = order
+            .get_path(&[Field(3), Field(7), Index(2), Key("zip".into())])
+            .unwrap();
+
+        // Then only that leaf should be returned
+        assert_eq!(zip, Some(ValueRef::String("60601")));
+        assert_eq!(
+            order.get_path(&[Field(3), Field(1)]).unwrap(),
+            Some(ValueRef::String("Ada"))
+        );
+        assert_eq!(
+            order.get_path(&[Field(1)]).unwrap(),
+            Some(ValueRef::Int64(1234))
+        );
+    }
+
+    #[test]
+    fn should_return_none_for_missing_segments() {
+        // Given an order nesting a customer with a list of addresses
+        let order = create_order();
+
+        // Then missing fields, indices and keys should lead nowhere
+        assert_eq!(order.get_path(&[Field(9)]).unwrap(), None);
+        assert_eq!(order.get_path(&[Field(3), Field(9)]).unwrap(), None);
+        assert_eq!(
+            order.get_path(&[Field(3), Field(7), Index(3)]).unwrap(),
+            None
+        );
+        assert_eq!(
+            order
+                .get_path(&[Field(3), Field(7), Index(0), Key("city".into())])
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            order
+                .get_path(&[Field(3), Field(7), Index(0), Key(1.into())])
+                .unwrap(),
+            None
+        );
+        assert_eq!(order.get_path(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn should_reject_segments_of_the_wrong_kind() {
+        // Given an order nesting a customer with a list of addresses
+        let order = create_order();
+
+        // When indexing into a row
+        let result = order.get_path(&[Field(3), Index(0)]);
+
+        // Then the offending segment should be reported
+        assert!(matches!(
+            result,
+            Err(ImprintError::InvalidPath {
+                segment: 1,
+                actual: TypeCode::Row
+            })
+        ));
+        assert!(matches!(
+            order.get_path(&[Field(1), Field(2)]),
+            Err(ImprintError::InvalidPath {
+                segment: 1,
+                actual: TypeCode::Int64
+            })
+        ));
+    }
+}
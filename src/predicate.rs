@@ -0,0 +1,460 @@
+use std::cmp::Ordering;
+use std::ops::Not;
+
+use bytes::BytesMut;
+
+use crate::{
+    error::ImprintError,
+    ops::RawField,
+    serde::Write,
+    types::{ImprintRecord, TypeCode, Value},
+    varint,
+};
+
+/// A filter over the fields of a record, evaluated against the encoded
+/// payload without decoding values.
+///
+/// Predicates on a field that is absent (or a tombstone) are false, except
+/// for [`Predicate::ne`] and negations, which are true.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate(Node);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Eq {
+        field_id: u16,
+        type_code: TypeCode,
+        bytes: Vec<u8>,
+    },
+    Cmp {
+        field_id: u16,
+        number: Number,
+        accept: [bool; 3],
+    },
+    Prefix {
+        field_id: u16,
+        bytes: Vec<u8>,
+    },
+    Contains {
+        field_id: u16,
+        bytes: Vec<u8>,
+    },
+    IsNull(u16),
+    Exists(u16),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+}
+
+/// A numeric value compared without regard to its width
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(field_id: u16, value: &Value) -> Result<Self, ImprintError> {
+        match *value {
+            Value::Int32(v) => Ok(Self::Int(v.into())),
+            Value::Int64(v) => Ok(Self::Int(v)),
+            Value::Float32(v) => Ok(Self::Float(v.into())),
+            Value::Float64(v) => Ok(Self::Float(v)),
+            _ => Err(ImprintError::TypeMismatch {
+                field_id,
+                expected: TypeCode::Int64,
+                actual: value.type_code(),
+            }),
+        }
+    }
+
+    /// Read a number from the encoded bytes of a numeric field
+    fn from_bytes(type_code: TypeCode, bytes: &[u8]) -> Option<Self> {
+        match type_code {
+            TypeCode::Int32 => Some(Self::Int(
+                i32::from_le_bytes(bytes.get(..4)?.try_into().ok()?).into(),
+            )),
+            TypeCode::Int64 => Some(Self::Int(i64::from_le_bytes(
+                bytes.get(..8)?.try_into().ok()?,
+            ))),
+            TypeCode::Float32 => Some(Self::Float(
+                f32::from_le_bytes(bytes.get(..4)?.try_into().ok()?).into(),
+            )),
+            TypeCode::Float64 => Some(Self::Float(f64::from_le_bytes(
+                bytes.get(..8)?.try_into().ok()?,
+            ))),
+            _ => None,
+        }
+    }
+
+    fn partial_cmp(self, other: Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(&b)),
+            (Self::Int(a), Self::Float(b)) => cmp_int_float(a, b),
+            (Self::Float(a), Self::Int(b)) => cmp_int_float(b, a).map(Ordering::reverse),
+            (Self::Float(a), Self::Float(b)) => a.partial_cmp(&b),
+        }
+    }
+}
+
+/// Compare an integer to a float exactly, without rounding the integer to the
+/// nearest float
+fn cmp_int_float(int: i64, float: f64) -> Option<Ordering> {
+    // 2^63, the first float past the range of i64
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if float.is_nan() {
+        return None;
+    }
+    if float >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if float < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+    // the whole part of the float is in range, so it converts exactly
+    let whole = float.trunc();
+    Some(int.cmp(&(whole as i64)).then(whole.partial_cmp(&float)?))
+}
+
+impl Predicate {
+    /// The field holds a value with the same type and encoding as `value`
+    pub fn eq(field_id: u16, value: impl Into<Value>) -> Result<Self, ImprintError> {
+        let value = value.into();
+        let mut buf = BytesMut::new();
+        value.write(&mut buf)?;
+        Ok(Self(Node::Eq {
+            field_id,
+            type_code: value.type_code(),
+            bytes: buf.to_vec(),
+        }))
+    }
+
+    /// The field is absent or doesn't hold `value`
+    pub fn ne(field_id: u16, value: impl Into<Value>) -> Result<Self, ImprintError> {
+        Ok(!Self::eq(field_id, value)?)
+    }
+
+    /// The field is numeric and less than `value`
+    pub fn lt(field_id: u16, value: impl Into<Value>) -> Result<Self, ImprintError> {
+        Self::cmp(field_id, value.into(), [true, false, false])
+    }
+
+    /// The field is numeric and less than or equal to `value`
+    pub fn le(field_id: u16, value: impl Into<Value>) -> Result<Self, ImprintError> {
+        Self::cmp(field_id, value.into(), [true, true, false])
+    }
+
+    /// The field is numeric and greater than `value`
+    pub fn gt(field_id: u16, value: impl Into<Value>) -> Result<Self, ImprintError> {
+        Self::cmp(field_id, value.into(), [false, false, true])
+    }
+
+    /// The field is numeric and greater than or equal to `value`
+    pub fn ge(field_id: u16, value: impl Into<Value>) -> Result<Self, ImprintError> {
+        Self::cmp(field_id, value.into(), [false, true, true])
+    }
+
+    /// The field is a string or bytes starting with `prefix`
+    pub fn prefix(field_id: u16, prefix: impl AsRef<[u8]>) -> Self {
+        Self(Node::Prefix {
+            field_id,
+            bytes: prefix.as_ref().to_vec(),
+        })
+    }
+
+    /// The field is a string or bytes containing `needle`
+    pub fn contains(field_id: u16, needle: impl AsRef<[u8]>) -> Self {
+        Self(Node::Contains {
+            field_id,
+            bytes: needle.as_ref().to_vec(),
+        })
+    }
+
+    /// The field is present and null
+    pub fn is_null(field_id: u16) -> Self {
+        Self(Node::IsNull(field_id))
+    }
+
+    /// The field is present
+    pub fn exists(field_id: u16) -> Self {
+        Self(Node::Exists(field_id))
+    }
+
+    /// Both this and `other` match
+    pub fn and(self, other: Predicate) -> Self {
+        match self.0 {
+            Node::And(mut nodes) => {
+                nodes.push(other.0);
+                Self(Node::And(nodes))
+            }
+            node => Self(Node::And(vec![node, other.0])),
+        }
+    }
+
+    /// Either this or `other` matches
+    pub fn or(self, other: Predicate) -> Self {
+        match self.0 {
+            Node::Or(mut nodes) => {
+                nodes.push(other.0);
+                Self(Node::Or(nodes))
+            }
+            node => Self(Node::Or(vec![node, other.0])),
+        }
+    }
+
+    /// Evaluate the predicate against a record
+    pub fn matches(&self, record: &ImprintRecord) -> Result<bool, ImprintError> {
+        self.0.matches(record)
+    }
+
+    fn cmp(field_id: u16, value: Value, accept: [bool; 3]) -> Result<Self, ImprintError> {
+        Ok(Self(Node::Cmp {
+            field_id,
+            number: Number::from_value(field_id, &value)?,
+            accept,
+        }))
+    }
+}
+
+impl Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Self::Output {
+        match self.0 {
+            Node::Not(node) => Self(*node),
+            node => Self(Node::Not(Box::new(node))),
+        }
+    }
+}
+
+impl Node {
+    fn matches(&self, record: &ImprintRecord) -> Result<bool, ImprintError> {
+        let field = |field_id| match record.live_index(field_id) {
+            Some(idx) => record.raw_field(idx).map(Some),
+            None => Ok(None),
+        };
+
+        match self {
+            Self::Eq {
+                field_id,
+                type_code,
+                bytes,
+            } => Ok(field(*field_id)?
+                .is_some_and(|f| f.type_code == *type_code && f.bytes == bytes.as_slice())),
+            Self::Cmp {
+                field_id,
+                number,
+                accept,
+            } => {
+                let Some(f) = field(*field_id)? else {
+                    return Ok(false);
+                };
+                let ordering = Number::from_bytes(f.type_code, f.bytes)
+                    .and_then(|value| value.partial_cmp(*number));
+                Ok(ordering.is_some_and(|o| accept[(o as i8 + 1) as usize]))
+            }
+            Self::Prefix { field_id, bytes } => {
+                Ok(contents(field(*field_id)?)?.is_some_and(|c| c.starts_with(bytes)))
+            }
+            Self::Contains { field_id, bytes } => Ok(contents(field(*field_id)?)?
+                .is_some_and(|c| bytes.is_empty() || c.windows(bytes.len()).any(|w| w == bytes))),
+            Self::IsNull(field_id) => {
+                Ok(field(*field_id)?.is_some_and(|f| f.type_code == TypeCode::Null))
+            }
+            Self::Exists(field_id) => Ok(record.contains(*field_id)),
+            Self::And(nodes) => {
+                for node in nodes {
+                    if !node.matches(record)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Self::Or(nodes) => {
+                for node in nodes {
+                    if node.matches(record)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Self::Not(node) => Ok(!node.matches(record)?),
+        }
+    }
+}
+
+/// The contents of a string or bytes field, without its length prefix
+fn contents(field: Option<RawField<'_>>) -> Result<Option<&[u8]>, ImprintError> {
+    let Some(field) = field else {
+        return Ok(None);
+    };
+    if !matches!(field.type_code, TypeCode::String | TypeCode::Bytes) {
+        return Ok(None);
+    }
+    let (len, len_size) = varint::decode_slice(field.bytes)?;
+    let contents = field.bytes.get(len_size..len_size + len as usize).ok_or(
+        ImprintError::BufferUnderflow {
+            needed: len as usize,
+            available: field.bytes.len() - len_size,
+        },
+    )?;
+    Ok(Some(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{create_record, create_update};
+
+    fn create_test_record() -> ImprintRecord {
+        create_update(
+            &[
+                (1, 42.into()),
+                (2, 7i64.into()),
+                (3, 1.5f64.into()),
+                (4, "processed".into()),
+                (5, vec![0xcau8, 0xfe, 0xba, 0xbe].into()),
+                (6, Value::Null),
+                (7, vec![1, 2, 3].into()),
+            ],
+            &[8],
+        )
+    }
+
+    #[test]
+    fn should_compare_encoded_values_for_equality() {
+        // Given a record with fields of several types
+        let record = create_test_record();
+
+        // Then equality should match on the encoded bytes
+        assert!(Predicate::eq(1, 42).unwrap().matches(&record).unwrap());
+        assert!(
+            Predicate::eq(4, "processed")
+                .unwrap()
+                .matches(&record)
+                .unwrap()
+        );
+        assert!(
+            Predicate::eq(7, vec![1, 2, 3])
+                .unwrap()
+                .matches(&record)
+                .unwrap()
+        );
+        assert!(
+            !Predicate::eq(4, "pending")
+                .unwrap()
+                .matches(&record)
+                .unwrap()
+        );
+
+        // equality is by type as well as value
+        assert!(!Predicate::eq(1, 42i64).unwrap().matches(&record).unwrap());
+
+        // absent fields are never equal, so always not-equal
+        assert!(!Predicate::eq(99, 42).unwrap().matches(&record).unwrap());
+        assert!(Predicate::ne(99, 42).unwrap().matches(&record).unwrap());
+        assert!(Predicate::ne(1, 43).unwrap().matches(&record).unwrap());
+        assert!(!Predicate::ne(1, 42).unwrap().matches(&record).unwrap());
+    }
+
+    #[test]
+    fn should_compare_numerics_across_widths() {
+        // Given a record with numeric fields of different widths
+        let record = create_test_record();
+
+        // Then comparisons should ignore the width of either side
+        assert!(Predicate::gt(1, 41i64).unwrap().matches(&record).unwrap());
+        assert!(Predicate::lt(2, 7.5f64).unwrap().matches(&record).unwrap());
+        assert!(Predicate::ge(3, 1.5f32).unwrap().matches(&record).unwrap());
+        assert!(Predicate::le(1, 42).unwrap().matches(&record).unwrap());
+        assert!(!Predicate::lt(1, 42).unwrap().matches(&record).unwrap());
+        assert!(!Predicate::gt(3, 2).unwrap().matches(&record).unwrap());
+
+        // integers past the precision of a float should compare exactly
+        let big = create_record(&[(2, ((1i64 << 53) + 1).into())]);
+        assert!(
+            !Predicate::le(2, (1i64 << 53) as f64)
+                .unwrap()
+                .matches(&big)
+                .unwrap()
+        );
+        assert!(
+            Predicate::gt(2, (1i64 << 53) as f64)
+                .unwrap()
+                .matches(&big)
+                .unwrap()
+        );
+        assert!(Predicate::lt(2, 1e19f64).unwrap().matches(&big).unwrap());
+        assert!(
+            !Predicate::lt(3, f64::NAN)
+                .unwrap()
+                .matches(&record)
+                .unwrap()
+        );
+        assert!(Predicate::gt(3, 1i64).unwrap().matches(&record).unwrap());
+
+        // non-numeric fields never compare
+        assert!(!Predicate::gt(4, 0).unwrap().matches(&record).unwrap());
+        assert!(Predicate::gt(1, "forty").is_err());
+    }
+
+    #[test]
+    fn should_match_strings_and_bytes() {
+        // Given a record with string and bytes fields
+        let record = create_test_record();
+
+        // Then prefixes and substrings should be matched on the contents
+        assert!(Predicate::prefix(4, "proc").matches(&record).unwrap());
+        assert!(!Predicate::prefix(4, "cess").matches(&record).unwrap());
+        assert!(Predicate::contains(4, "cess").matches(&record).unwrap());
+        assert!(Predicate::prefix(5, [0xca, 0xfe]).matches(&record).unwrap());
+        assert!(
+            Predicate::contains(5, [0xfe, 0xba])
+                .matches(&record)
+                .unwrap()
+        );
+        assert!(
+            !Predicate::contains(5, [0xbe, 0xca])
+                .matches(&record)
+                .unwrap()
+        );
+        assert!(!Predicate::prefix(1, "4").matches(&record).unwrap());
+    }
+
+    #[test]
+    fn should_check_presence_and_nulls() {
+        // Given a record with a null field and a tombstone
+        let record = create_test_record();
+
+        // Then nulls and presence should be reported
+        assert!(Predicate::is_null(6).matches(&record).unwrap());
+        assert!(!Predicate::is_null(1).matches(&record).unwrap());
+        assert!(!Predicate::is_null(99).matches(&record).unwrap());
+        assert!(Predicate::exists(6).matches(&record).unwrap());
+        assert!(!Predicate::exists(99).matches(&record).unwrap());
+
+        // tombstones are absent
+        assert!(!Predicate::exists(8).matches(&record).unwrap());
+        assert!(!Predicate::is_null(8).matches(&record).unwrap());
+    }
+
+    #[test]
+    fn should_combine_predicates() {
+        // Given a record and two predicates on it
+        let record = create_test_record();
+        let status = Predicate::eq(4, "processed").unwrap();
+        let small = Predicate::lt(1, 10).unwrap();
+
+        // Then they should combine like boolean expressions
+        assert!(!status.clone().and(small.clone()).matches(&record).unwrap());
+        assert!(status.clone().or(small.clone()).matches(&record).unwrap());
+        assert!(status.clone().and(!small.clone()).matches(&record).unwrap());
+        assert!(
+            !(!status.clone())
+                .or(small.clone())
+                .matches(&record)
+                .unwrap()
+        );
+        assert_eq!(!!status.clone(), status);
+    }
+}
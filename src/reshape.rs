@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use bytes::BytesMut;
+
+use crate::{
+    error::ImprintError,
+    ops::{Merge, MergeOptions, Project},
+    serde::{Read, Write},
+    types::{DirectoryEntry, Flags, ImprintRecord, SchemaId, TypeCode},
+};
+
+/// A user callback assigning a field id to the fieldspace of a split part
+pub type FieldAssigner = dyn Fn(u16) -> Option<u32> + Send + Sync;
+
+/// How [`ImprintRecord::split`] assigns fields to parts, each identified by
+/// the fieldspace id of the record it becomes. Fields assigned to no part are
+/// dropped, and when buckets overlap the first matching one wins.
+#[derive(Clone)]
+pub enum Partitioner {
+    /// Assign the ids within each range to its fieldspace
+    IdRanges(Vec<(RangeInclusive<u16>, u32)>),
+    /// Assign the ids in each set to its fieldspace
+    IdSets(Vec<(Vec<u16>, u32)>),
+    /// Let a callback assign each id
+    Custom(Arc<FieldAssigner>),
+}
+
+impl fmt::Debug for Partitioner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IdRanges(ranges) => write!(f, "IdRanges({ranges:?})"),
+            Self::IdSets(sets) => write!(f, "IdSets({sets:?})"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl Partitioner {
+    /// The fieldspace of the part a field belongs to, if any
+    fn assign(&self, field_id: u16) -> Option<u32> {
+        match self {
+            Self::IdRanges(ranges) => ranges
+                .iter()
+                .find(|(range, _)| range.contains(&field_id))
+                .map(|&(_, fieldspace_id)| fieldspace_id),
+            Self::IdSets(sets) => sets
+                .iter()
+                .find(|(ids, _)| ids.contains(&field_id))
+                .map(|&(_, fieldspace_id)| fieldspace_id),
+            Self::Custom(assigner) => assigner(field_id),
+        }
+    }
+}
+
+impl ImprintRecord {
+    /// Lift the fields of a nested row into this record, replacing the row.
+    ///
+    /// Nested fields named by the `(from_id, to_id)` pairs of `mapping` are
+    /// renumbered and the rest keep their ids. The lifted fields are then
+    /// merged into this record as the right-hand side of
+    /// [`Merge::merge_with`], so `options` decides between a nested field and
+    /// a parent field with the same id. Values are copied as raw slices of
+    /// the nested payload without being decoded.
+    pub fn flatten(
+        &self,
+        field_id: u16,
+        mapping: &[(u16, u16)],
+        options: &MergeOptions,
+    ) -> Result<ImprintRecord, ImprintError> {
+        let idx = self
+            .live_index(field_id)
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))?;
+        let type_code = self.directory[idx].type_code;
+        if type_code != TypeCode::Row {
+            return Err(ImprintError::TypeMismatch {
+                field_id,
+                expected: TypeCode::Row,
+                actual: type_code,
+            });
+        }
+        let (nested, _) = ImprintRecord::read(self.payload.slice(self.field_range(idx)?))?;
+
+        let mapping: Vec<_> = nested
+            .fields()
+            .map(|(id, _)| {
+                let to = mapping.iter().find(|&&(from, _)| from == id);
+                (id, to.map_or(id, |&(_, to)| to))
+            })
+            .collect();
+        let lifted = nested.project_remap(&mapping, self.header.schema_id.fieldspace_id)?;
+
+        self.without_field(field_id)?.merge_with(&lifted, options)
+    }
+
+    /// Move the given fields into a new nested row stored at `new_field_id`,
+    /// leaving the rest of the record untouched. Ids that aren't present are
+    /// ignored.
+    ///
+    /// As with [`ImprintWriter`](crate::ImprintWriter), the nested row takes
+    /// the fieldspace id of `nested_schema_id` and its schema hash is computed
+    /// from the moved fields. Values are copied as raw slices without being
+    /// decoded. `new_field_id` may reuse the id of a moved field, but not one
+    /// that stays in the record.
+    pub fn nest(
+        &self,
+        field_ids: &[u16],
+        new_field_id: u16,
+        nested_schema_id: SchemaId,
+    ) -> Result<ImprintRecord, ImprintError> {
+        if self.contains(new_field_id) && !field_ids.contains(&new_field_id) {
+            return Err(ImprintError::DuplicateFieldId(new_field_id));
+        }
+
+        let moved = self.project(field_ids)?;
+        let nested = ImprintRecord::from_parts(
+            Flags::new(0),
+            nested_schema_id.fieldspace_id,
+            moved.directory.to_vec(),
+            moved.payload,
+        );
+        let mut buf = BytesMut::new();
+        nested.write(&mut buf)?;
+
+        self.without_fields(field_ids)?
+            .with_raw_field(new_field_id, TypeCode::Row, buf.freeze())
+    }
+
+    /// Split this record into one part per fieldspace the partitioner assigns
+    /// fields to, keyed by fieldspace id. Each part gets the schema id of its
+    /// own fields and is built by copying slices of the payload, without
+    /// decoding any values. Parts that receive no fields are omitted.
+    pub fn split(
+        &self,
+        partitioner: &Partitioner,
+    ) -> Result<BTreeMap<u32, ImprintRecord>, ImprintError> {
+        let mut parts: BTreeMap<u32, (Vec<DirectoryEntry>, BytesMut)> = BTreeMap::new();
+        for (idx, entry) in self.directory.iter().enumerate() {
+            let Some(fieldspace_id) = partitioner.assign(entry.id) else {
+                continue;
+            };
+            let range = self.field_range(idx)?;
+            let (directory, payload) = parts.entry(fieldspace_id).or_default();
+            directory.push(DirectoryEntry {
+                offset: payload.len() as u32,
+                ..entry.clone()
+            });
+            payload.extend_from_slice(&self.payload[range]);
+        }
+
+        Ok(parts
+            .into_iter()
+            .map(|(fieldspace_id, (directory, payload))| {
+                let part = ImprintRecord::from_parts(
+                    self.header.flags.without(Flags::DELTA),
+                    fieldspace_id,
+                    directory,
+                    payload.freeze(),
+                );
+                (fieldspace_id, part)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{create_record, create_record_in};
+    use crate::{ConflictStrategy, ImprintWriter};
+
+    fn create_order() -> ImprintRecord {
+        let customer = create_record(&[
+            (1, 77i64.into()),
+            (2, "Ada".into()),
+            (3, "ada@example.com".into()),
+        ]);
+        create_record(&[
+            (1, 1234i64.into()),
+            (2, "pending".into()),
+            (5, customer.into()),
+        ])
+    }
+
+    #[test]
+    fn should_lift_nested_fields_under_remapped_ids() {
+        // Given an order with a joined customer row
+        let order = create_order();
+
+        // When flattening the customer, moving its clashing ids out of the way
+        let flat = order
+            .flatten(5, &[(1, 10), (2, 11)], &MergeOptions::default())
+            .unwrap();
+
+        // Then the customer's fields should sit alongside the order's
+        assert_eq!(
+            flat,
+            create_record(&[
+                (1, 1234i64.into()),
+                (2, "pending".into()),
+                (3, "ada@example.com".into()),
+                (10, 77i64.into()),
+                (11, "Ada".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_resolve_clashing_ids_with_merge_options() {
+        // Given an order with a joined customer row
+        let order = create_order();
+
+        // When flattening without remapping the clashing ids
+        let prefer_parent = order.flatten(5, &[], &MergeOptions::default()).unwrap();
+        let prefer_nested = order
+            .flatten(
+                5,
+                &[],
+                &MergeOptions::with_conflict(ConflictStrategy::PreferRight),
+            )
+            .unwrap();
+        let strict = order.flatten(
+            5,
+            &[],
+            &MergeOptions::with_conflict(ConflictStrategy::ErrorOnConflict),
+        );
+
+        // Then the conflict strategy should pick between parent and nested fields
+        assert_eq!(prefer_parent.get_value(2).unwrap(), Some("pending".into()));
+        assert_eq!(prefer_nested.get_value(2).unwrap(), Some("Ada".into()));
+        assert!(!prefer_nested.contains(5));
+        assert!(matches!(strict, Err(ImprintError::MergeConflict(1))));
+    }
+
+    #[test]
+    fn should_nest_fields_into_sub_row() {
+        // Given a record with enrichment columns alongside its own fields
+        let record = create_record(&[
+            (1, 1234i64.into()),
+            (2, "pending".into()),
+            (7, "US".into()),
+            (8, 0.25f64.into()),
+        ]);
+
+        // When folding the enrichment columns into a sub-record
+        let schema_id = SchemaId {
+            fieldspace_id: 9,
+            schema_hash: 0,
+        };
+        let nested = record.nest(&[7, 8, 99], 5, schema_id).unwrap();
+
+        // Then they should be moved under the new field
+        let mut writer = ImprintWriter::new(schema_id).unwrap();
+        writer.add_field(7, "US".into()).unwrap();
+        writer.add_field(8, 0.25f64.into()).unwrap();
+        let enrichment = writer.build().unwrap();
+        assert_eq!(
+            nested,
+            create_record(&[
+                (1, 1234i64.into()),
+                (2, "pending".into()),
+                (5, enrichment.into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_undo_flatten_with_nest() {
+        // Given an order with a joined customer row whose ids don't clash
+        let customer = create_record(&[(10, 77i64.into()), (11, "Ada".into())]);
+        let order = create_record(&[(1, 1234i64.into()), (5, customer.clone().into())]);
+
+        // When flattening the customer and nesting its fields back
+        let flat = order.flatten(5, &[], &MergeOptions::default()).unwrap();
+        let renested = flat.nest(&[10, 11], 5, customer.schema_id()).unwrap();
+
+        // Then the original order should be restored
+        assert_eq!(flat.fields().count(), 3);
+        assert_eq!(renested, order);
+    }
+
+    #[test]
+    fn should_reject_nesting_over_a_remaining_field() {
+        // Given an order with a joined customer row
+        let order = create_order();
+
+        // Then the new field may replace a moved field but not a remaining one
+        assert!(order.nest(&[1, 2], 1, order.schema_id()).is_ok());
+        assert!(matches!(
+            order.nest(&[1], 2, order.schema_id()),
+            Err(ImprintError::DuplicateFieldId(2))
+        ));
+    }
+
+    fn create_merged() -> ImprintRecord {
+        create_record(&[
+            (1, 1234i64.into()),
+            (2, "pending".into()),
+            (100, "US".into()),
+            (101, 0.25f64.into()),
+            (200, true.into()),
+        ])
+    }
+
+    #[test]
+    fn should_split_by_id_ranges() {
+        // Given a record merged from several sources
+        let merged = create_merged();
+
+        // When splitting it by the id range each source owns
+        let parts = merged
+            .split(&Partitioner::IdRanges(vec![
+                (0..=99, 10),
+                (100..=199, 20),
+                (300..=399, 30),
+            ]))
+            .unwrap();
+
+        // Then each source should get its own record, dropping unassigned fields
+        assert_eq!(
+            parts,
+            BTreeMap::from([
+                (
+                    10,
+                    create_record_in(10, &[(1, 1234i64.into()), (2, "pending".into())])
+                ),
+                (
+                    20,
+                    create_record_in(20, &[(100, "US".into()), (101, 0.25f64.into())])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_split_by_id_sets_and_callback() {
+        // Given a record merged from several sources
+        let merged = create_merged();
+
+        // When splitting it by explicit id sets, with the first set winning
+        let by_sets = merged
+            .split(&Partitioner::IdSets(vec![
+                (vec![1, 200], 10),
+                (vec![1, 2, 100, 101], 20),
+            ]))
+            .unwrap();
+
+        // Then each set should become a part
+        assert_eq!(
+            by_sets[&10],
+            create_record_in(10, &[(1, 1234i64.into()), (200, true.into())])
+        );
+        assert_eq!(
+            by_sets[&20],
+            create_record_in(
+                20,
+                &[
+                    (2, "pending".into()),
+                    (100, "US".into()),
+                    (101, 0.25f64.into())
+                ]
+            )
+        );
+
+        // And a callback should be able to assign every field
+        let by_parity = merged
+            .split(&Partitioner::Custom(Arc::new(|id| Some(u32::from(id % 2)))))
+            .unwrap();
+        assert_eq!(by_parity[&0].fields().count(), 3);
+        assert_eq!(by_parity[&1].fields().count(), 2);
+        assert_eq!(by_parity[&1].schema_id().fieldspace_id, 1);
+    }
+
+    #[test]
+    fn should_undo_merge_with_split() {
+        // Given parts from two sources
+        let left = create_record_in(10, &[(1, 1234i64.into()), (2, "pending".into())]);
+        let right = create_record_in(20, &[(100, "US".into())]);
+
+        // When merging them and splitting the result by source
+        let merged = left.merge(&right).unwrap();
+        let parts = merged
+            .split(&Partitioner::IdRanges(vec![(0..=99, 10), (100..=199, 20)]))
+            .unwrap();
+
+        // Then the original parts should be restored
+        assert_eq!(parts, BTreeMap::from([(10, left), (20, right)]));
+    }
+
+    #[test]
+    fn should_not_mark_parts_of_a_delta_as_deltas() {
+        // Given a delta record
+        let base = create_record(&[(1, 1.into())]);
+        let delta = base
+            .with_field(1, 2.into())
+            .unwrap()
+            .delta_from(&base)
+            .unwrap();
+
+        // When splitting it
+        let parts = delta
+            .split(&Partitioner::IdRanges(vec![(0..=u16::MAX, 1)]))
+            .unwrap();
+
+        // Then the part should hold the same fields but not claim to be a delta
+        assert!(!parts[&1].is_delta());
+        assert_eq!(parts[&1].fields().count(), delta.fields().count());
+    }
+
+    #[test]
+    fn should_reject_flattening_missing_or_non_row_fields() {
+        // Given an order with a joined customer row
+        let order = create_order();
+
+        // Then only a present row field can be flattened
+        assert!(matches!(
+            order.flatten(9, &[], &MergeOptions::default()),
+            Err(ImprintError::FieldNotFound(9))
+        ));
+        assert!(matches!(
+            order.flatten(2, &[], &MergeOptions::default()),
+            Err(ImprintError::TypeMismatch {
+                field_id: 2,
+                expected: TypeCode::Row,
+                actual: TypeCode::String
+            })
+        ));
+    }
+}
@@ -1,17 +1,20 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 use crate::{
     MAGIC, VERSION,
+    cache::DirectoryCache,
     error::ImprintError,
+    limits::DecodeLimits,
     types::{DirectoryEntry, Flags, Header, ImprintRecord, MapKey, SchemaId, TypeCode, Value},
     varint,
 };
 
-const HEADER_BYTES: usize = 15;
+pub(crate) const HEADER_BYTES: usize = 15;
 const DIR_COUNT_BYTES: usize = 5;
-const DIR_ENTRY_BYTES: usize = 7;
+pub(crate) const DIR_ENTRY_BYTES: usize = 7;
 
 /// A trait for types that can be written to a byte buffer
 pub trait Write {
@@ -93,11 +96,16 @@ impl Write for Value {
                     return Ok(());
                 }
 
-                let key_type_code = m.keys().next().unwrap().type_code();
-                let value_type_code = m.values().next().unwrap().type_code();
+                // entries are written in key order so that equal maps always
+                // produce the same bytes regardless of hash iteration order
+                let mut entries: Vec<_> = m.iter().collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+                let key_type_code = entries[0].0.type_code();
+                let value_type_code = entries[0].1.type_code();
                 buf.put_u8(key_type_code as u8);
                 buf.put_u8(value_type_code as u8);
-                for (key, value) in m {
+                for (key, value) in entries {
                     if key.type_code() != key_type_code {
                         return Err(ImprintError::SchemaError(format!(
                             "map keys must have same type code: {:?} != {:?}",
@@ -149,7 +157,67 @@ impl Write for MapKey {
 }
 
 impl ValueRead for Value {
-    fn read(type_code: TypeCode, mut bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+    fn read(type_code: TypeCode, bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+        Value::read_with_limits(type_code, bytes, &DecodeLimits::default())
+    }
+}
+
+impl Value {
+    /// Read a value from the buffer with a known type code, enforcing the given
+    /// limits, and returning the value and number of bytes read
+    pub fn read_with_limits(
+        type_code: TypeCode,
+        bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<(Self, usize), ImprintError> {
+        Decoder::new(limits).read_value(type_code, bytes)
+    }
+}
+
+/// Decodes values while tracking nesting depth and allocations against a set
+/// of [`DecodeLimits`], so malformed input fails with an error instead of
+/// exhausting the stack or memory
+pub(crate) struct Decoder<'l> {
+    limits: &'l DecodeLimits,
+    depth: usize,
+    allocated: usize,
+}
+
+impl<'l> Decoder<'l> {
+    pub(crate) fn new(limits: &'l DecodeLimits) -> Self {
+        Self {
+            limits,
+            depth: 0,
+            allocated: 0,
+        }
+    }
+
+    fn read_value(
+        &mut self,
+        type_code: TypeCode,
+        bytes: Bytes,
+    ) -> Result<(Value, usize), ImprintError> {
+        if matches!(type_code, TypeCode::Array | TypeCode::Map | TypeCode::Row) {
+            if self.depth >= self.limits.max_depth {
+                return Err(ImprintError::LimitExceeded {
+                    limit: "max_depth",
+                    max: self.limits.max_depth,
+                });
+            }
+            self.depth += 1;
+            let result = self.read_value_inner(type_code, bytes);
+            self.depth -= 1;
+            result
+        } else {
+            self.read_value_inner(type_code, bytes)
+        }
+    }
+
+    fn read_value_inner(
+        &mut self,
+        type_code: TypeCode,
+        mut bytes: Bytes,
+    ) -> Result<(Value, usize), ImprintError> {
         let mut bytes_read = 0;
 
         let value = match type_code {
@@ -224,6 +292,8 @@ impl ValueRead for Value {
                         available: bytes.remaining(),
                     });
                 }
+                self.limits.check_bytes_len(len)?;
+                self.allocate(len as usize)?;
                 let mut v = vec![0; len as usize];
                 bytes.copy_to_slice(&mut v);
                 bytes_read += len as usize;
@@ -240,6 +310,8 @@ impl ValueRead for Value {
                         available: bytes.remaining(),
                     });
                 }
+                self.limits.check_bytes_len(len)?;
+                self.allocate(len as usize)?;
                 let mut v = vec![0; len as usize];
                 bytes.copy_to_slice(&mut v);
                 bytes_read += len as usize;
@@ -255,12 +327,16 @@ impl ValueRead for Value {
                     return Ok((Value::Array(vec![]), bytes_read));
                 }
 
-                let element_type = TypeCode::try_from(bytes.get_u8())?;
+                self.limits.check_elements(len)?;
+                self.allocate(len as usize * size_of::<Value>())?;
+                let element_type = TypeCode::try_from(read_u8(&mut bytes)?)?;
                 bytes_read += 1;
 
-                let mut values = Vec::with_capacity(len as usize);
+                // every element but null takes at least one byte, so the
+                // remaining input bounds how many can actually be present
+                let mut values = Vec::with_capacity((len as usize).min(bytes.remaining()));
                 for _ in 0..len {
-                    let (value, value_size) = Self::read(element_type, bytes.clone())?;
+                    let (value, value_size) = self.read_value(element_type, bytes.clone())?;
                     bytes.advance(value_size);
                     bytes_read += value_size;
                     values.push(value);
@@ -276,19 +352,22 @@ impl ValueRead for Value {
                     return Ok((Value::Map(HashMap::new()), bytes_read));
                 }
 
-                let key_type = TypeCode::try_from(bytes.get_u8())?;
+                self.limits.check_elements(len)?;
+                self.allocate(len as usize * (size_of::<MapKey>() + size_of::<Value>()))?;
+                let key_type = TypeCode::try_from(read_u8(&mut bytes)?)?;
                 bytes_read += 1;
 
-                let value_type = TypeCode::try_from(bytes.get_u8())?;
+                let value_type = TypeCode::try_from(read_u8(&mut bytes)?)?;
                 bytes_read += 1;
 
-                let mut map = HashMap::with_capacity(len as usize);
+                let mut map = HashMap::with_capacity((len as usize).min(bytes.remaining()));
                 for _ in 0..len {
-                    let (key, key_size) = MapKey::read(key_type, bytes.clone())?;
+                    let (key, key_size) = self.read_value(key_type, bytes.clone())?;
+                    let key = MapKey::try_from(key)?;
                     bytes.advance(key_size);
                     bytes_read += key_size;
 
-                    let (value, value_size) = Self::read(value_type, bytes.clone())?;
+                    let (value, value_size) = self.read_value(value_type, bytes.clone())?;
                     bytes.advance(value_size);
                     bytes_read += value_size;
 
@@ -297,20 +376,93 @@ impl ValueRead for Value {
                 map.into()
             }
             TypeCode::Row => {
-                let (record, size) = ImprintRecord::read(bytes)?;
+                let (record, size) = self.read_record(bytes, None)?;
                 bytes_read += size;
                 record.into()
             }
+            TypeCode::Tombstone => return Err(ImprintError::InvalidFieldType(type_code as u8)),
         };
         Ok((value, bytes_read))
     }
+
+    /// Read a record, taking its directory from the cache when the cache already
+    /// holds an identical directory for the record's schema id
+    pub(crate) fn read_record(
+        &mut self,
+        mut bytes: Bytes,
+        cache: Option<&DirectoryCache>,
+    ) -> Result<(ImprintRecord, usize), ImprintError> {
+        let mut bytes_read = 0;
+
+        let (header, header_size) = Header::read(bytes.clone())?;
+        bytes.advance(header_size);
+        bytes_read += header_size;
+
+        let (count, count_size) = varint::decode(bytes.clone())?;
+        bytes.advance(count_size);
+        bytes_read += count_size;
+
+        let directory_size = count as usize * DIR_ENTRY_BYTES;
+        if bytes.remaining() < directory_size {
+            return Err(ImprintError::BufferUnderflow {
+                needed: directory_size,
+                available: bytes.remaining(),
+            });
+        }
+        self.limits.check_elements(count)?;
+        let raw_directory = bytes.split_to(directory_size);
+        bytes_read += directory_size;
+
+        let directory = match cache {
+            Some(cache) => cache.get_or_parse(header.schema_id, raw_directory)?,
+            None => {
+                self.allocate(count as usize * size_of::<DirectoryEntry>())?;
+                read_directory(raw_directory)?
+            }
+        };
+
+        let payload_size = header.payload_size as usize;
+        if bytes.remaining() < payload_size {
+            return Err(ImprintError::BufferUnderflow {
+                needed: payload_size,
+                available: bytes.remaining(),
+            });
+        }
+        let payload = bytes.split_to(payload_size);
+        bytes_read += payload_size;
+
+        Ok((
+            ImprintRecord {
+                header,
+                directory,
+                payload,
+            },
+            bytes_read,
+        ))
+    }
+
+    fn allocate(&mut self, size: usize) -> Result<(), ImprintError> {
+        match self.allocated.checked_add(size) {
+            Some(total) if total <= self.limits.max_allocation => {
+                self.allocated = total;
+                Ok(())
+            }
+            _ => Err(ImprintError::LimitExceeded {
+                limit: "max_allocation",
+                max: self.limits.max_allocation,
+            }),
+        }
+    }
 }
 
-impl ValueRead for MapKey {
-    fn read(type_code: TypeCode, bytes: Bytes) -> Result<(Self, usize), ImprintError> {
-        let (value, size) = Value::read(type_code, bytes.clone())?;
-        Ok((MapKey::try_from(value)?, size))
+fn read_u8(bytes: &mut Bytes) -> Result<u8, ImprintError> {
+    if !bytes.has_remaining() {
+        return Err(ImprintError::BufferUnderflow {
+            needed: 1,
+            available: 0,
+        });
     }
+    Ok(bytes.get_u8())
 }
 
 impl Write for DirectoryEntry {
@@ -323,27 +475,29 @@ impl Write for DirectoryEntry {
 }
 
 impl Read for DirectoryEntry {
-    fn read(mut bytes: Bytes) -> Result<(Self, usize), ImprintError> {
-        if bytes.remaining() < DIR_ENTRY_BYTES {
-            return Err(ImprintError::BufferUnderflow {
-                needed: DIR_ENTRY_BYTES,
-                available: bytes.remaining(),
-            });
-        }
-
-        let id = bytes.get_u16_le();
-        let type_code = TypeCode::try_from(bytes.get_u8())?;
-        let offset = bytes.get_u32_le();
+    fn read(bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+        Ok((read_directory_entry(&bytes)?, DIR_ENTRY_BYTES))
+    }
+}
 
-        Ok((
-            Self {
-                id,
-                type_code,
-                offset,
-            },
-            DIR_ENTRY_BYTES,
-        ))
+/// Read a directory entry from the start of a slice
+pub(crate) fn read_directory_entry(mut bytes: &[u8]) -> Result<DirectoryEntry, ImprintError> {
+    if bytes.remaining() < DIR_ENTRY_BYTES {
+        return Err(ImprintError::BufferUnderflow {
+            needed: DIR_ENTRY_BYTES,
+            available: bytes.remaining(),
+        });
     }
+
+    let id = bytes.get_u16_le();
+    let type_code = TypeCode::try_from(bytes.get_u8())?;
+    let offset = bytes.get_u32_le();
+
+    Ok(DirectoryEntry {
+        id,
+        type_code,
+        offset,
+    })
 }
 
 impl Write for SchemaId {
@@ -388,39 +542,43 @@ impl Write for Header {
 }
 
 impl Read for Header {
-    fn read(mut bytes: Bytes) -> Result<(Self, usize), ImprintError> {
-        if bytes.remaining() < HEADER_BYTES {
-            return Err(ImprintError::BufferUnderflow {
-                needed: HEADER_BYTES,
-                available: bytes.remaining(),
-            });
-        }
-
-        let magic = bytes.get_u8();
-        if magic != MAGIC {
-            return Err(ImprintError::InvalidMagic(magic));
-        }
-
-        let version = bytes.get_u8();
-        if version != VERSION {
-            return Err(ImprintError::UnsupportedVersion(version));
-        }
+    fn read(bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+        Ok((read_header(&bytes)?, HEADER_BYTES))
+    }
+}
 
-        let flags = Flags::new(bytes.get_u8());
-        let (schema_id, _) = SchemaId::read(bytes.clone())?;
-        bytes.advance(8);
+/// Read a record header from the start of a slice
+pub(crate) fn read_header(mut bytes: &[u8]) -> Result<Header, ImprintError> {
+    if bytes.remaining() < HEADER_BYTES {
+        return Err(ImprintError::BufferUnderflow {
+            needed: HEADER_BYTES,
+            available: bytes.remaining(),
+        });
+    }
 
-        let payload_size = bytes.get_u32_le();
+    let magic = bytes.get_u8();
+    if magic != MAGIC {
+        return Err(ImprintError::InvalidMagic(magic));
+    }
 
-        Ok((
-            Self {
-                flags,
-                schema_id,
-                payload_size,
-            },
-            HEADER_BYTES,
-        ))
+    let version = bytes.get_u8();
+    if version != VERSION {
+        return Err(ImprintError::UnsupportedVersion(version));
     }
+
+    let flags = Flags::new(bytes.get_u8());
+    let fieldspace_id = bytes.get_u32_le();
+    let schema_hash = bytes.get_u32_le();
+    let payload_size = bytes.get_u32_le();
+
+    Ok(Header {
+        flags,
+        schema_id: SchemaId {
+            fieldspace_id,
+            schema_hash,
+        },
+        payload_size,
+    })
 }
 
 impl Write for ImprintRecord {
@@ -436,7 +594,7 @@ impl Write for ImprintRecord {
         self.header.write(buf)?;
 
         varint::encode(self.directory.len() as u32, buf);
-        for entry in &self.directory {
+        for entry in self.directory.iter() {
             entry.write(buf)?;
         }
 
@@ -447,43 +605,39 @@ impl Write for ImprintRecord {
 }
 
 impl Read for ImprintRecord {
-    fn read(mut bytes: Bytes) -> Result<(Self, usize), ImprintError> {
-        let mut bytes_read = 0;
-
-        let (header, header_size) = Header::read(bytes.clone())?;
-        bytes.advance(header_size);
-        bytes_read += header_size;
-
-        let mut directory = Vec::new();
-        let (count, count_size) = varint::decode(bytes.clone())?;
-        bytes.advance(count_size);
-        bytes_read += count_size;
-
-        for _ in 0..count {
-            let (entry, entry_size) = DirectoryEntry::read(bytes.clone())?;
-            bytes.advance(entry_size);
-            bytes_read += entry_size;
-            directory.push(entry);
-        }
+    fn read(bytes: Bytes) -> Result<(Self, usize), ImprintError> {
+        ImprintRecord::read_with_limits(bytes, &DecodeLimits::default())
+    }
+}
 
-        let payload = bytes.slice(..header.payload_size as usize);
-        bytes.advance(header.payload_size as usize);
-        bytes_read += header.payload_size as usize;
+impl ImprintRecord {
+    /// Read a record from the buffer, enforcing the given limits, and
+    /// returning the record and number of bytes read
+    pub fn read_with_limits(
+        bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<(Self, usize), ImprintError> {
+        Decoder::new(limits).read_record(bytes, None)
+    }
+}
 
-        Ok((
-            Self {
-                header,
-                directory,
-                payload,
-            },
-            bytes_read,
-        ))
+/// Parse the directory entries of an encoded directory (without its count)
+pub(crate) fn read_directory(mut bytes: Bytes) -> Result<Arc<[DirectoryEntry]>, ImprintError> {
+    let mut directory = Vec::with_capacity(bytes.len() / DIR_ENTRY_BYTES);
+    while bytes.has_remaining() {
+        let (entry, entry_size) = DirectoryEntry::read(bytes.clone())?;
+        bytes.advance(entry_size);
+        directory.push(entry);
     }
+    Ok(directory.into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ops::{Merge, Project};
+    use crate::test_support::encode_record;
+    use crate::types::schema_hash;
     use crate::writer::ImprintWriter;
     use proptest::prelude::*;
     use proptest::strategy::{BoxedStrategy, Strategy};
@@ -607,7 +761,9 @@ mod tests {
             schema_hash: 0xdeadbeef,
         })
         .unwrap();
-        outer_writer.add_field(1, inner_record.into()).unwrap();
+        outer_writer
+            .add_field(1, inner_record.clone().into())
+            .unwrap();
         outer_writer.add_field(2, 123i64.into()).unwrap();
         let outer_record = outer_writer.build().unwrap();
 
@@ -617,8 +773,11 @@ mod tests {
         let (deserialized_record, _) = ImprintRecord::read(buf.freeze()).unwrap();
 
         // Then the outer record metadata should be preserved
+        assert_eq!(
+            deserialized_record.header.schema_id,
+            outer_record.header.schema_id
+        );
         assert_eq!(deserialized_record.header.schema_id.fieldspace_id, 1);
-        assert_eq!(deserialized_record.header.schema_id.schema_hash, 0xdeadbeef);
         assert_eq!(deserialized_record.header.flags, Flags::new(0));
         assert_eq!(deserialized_record.directory.len(), 2);
 
@@ -629,8 +788,8 @@ mod tests {
 
         // And the inner record should be preserved
         if let Value::Row(inner) = got_row {
+            assert_eq!(inner.header.schema_id, inner_record.header.schema_id);
             assert_eq!(inner.header.schema_id.fieldspace_id, 2);
-            assert_eq!(inner.header.schema_id.schema_hash, 0xcafebabe);
             assert_eq!(inner.header.flags, Flags::new(0));
             assert_eq!(inner.directory.len(), 2);
 
@@ -681,7 +840,7 @@ mod tests {
 
             // Verify metadata
             prop_assert_eq!(record.header.schema_id.fieldspace_id, 1);
-            prop_assert_eq!(record.header.schema_id.schema_hash, 0xdeadbeef);
+            prop_assert_eq!(record.header.schema_id.schema_hash, schema_hash(&record.directory));
             prop_assert_eq!(record.header.flags, Flags::new(0));
             prop_assert_eq!(record.directory.len(), 8);
 
@@ -818,6 +977,257 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_write_equal_maps_as_equal_bytes() {
+        // Given two equal maps built with different capacities and insertion
+        // orders, so their hash iteration orders differ
+        let keys: Vec<i32> = (0..64).collect();
+        let mut forward = HashMap::with_capacity(64);
+        for k in &keys {
+            forward.insert(MapKey::Int32(*k), Value::Int32(k * 2));
+        }
+        let mut backward = HashMap::with_capacity(1024);
+        for k in keys.iter().rev() {
+            backward.insert(MapKey::Int32(*k), Value::Int32(k * 2));
+        }
+        assert_eq!(forward, backward);
+
+        // When writing both
+        let mut forward_buf = BytesMut::new();
+        Value::Map(forward).write(&mut forward_buf).unwrap();
+        let mut backward_buf = BytesMut::new();
+        Value::Map(backward).write(&mut backward_buf).unwrap();
+
+        // Then the bytes should be identical and canonically ordered
+        assert_eq!(forward_buf, backward_buf);
+        let (map, _) = crate::ValueRef::read(TypeCode::Map, &forward_buf).unwrap();
+        assert!(map.is_canonical().unwrap());
+    }
+
+    #[test]
+    fn should_error_on_truncated_payload() {
+        // Given a record whose payload is cut short
+        let bytes = encode_record(&[(1, "hello".into())]);
+        let truncated = bytes.slice(..bytes.len() - 2);
+
+        // When reading it
+        // Then it should return a buffer underflow error instead of panicking
+        assert!(matches!(
+            ImprintRecord::read(truncated),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+    }
+
+    #[test]
+    fn should_error_on_missing_collection_type_codes() {
+        // Given a non-empty array and map without their type codes
+        // Then reading them should fail instead of panicking
+        assert!(matches!(
+            Value::read(TypeCode::Array, Bytes::from_static(&[1])),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+        assert!(matches!(
+            Value::read(
+                TypeCode::Map,
+                Bytes::from_static(&[1, TypeCode::Int32 as u8])
+            ),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+    }
+
+    #[test]
+    fn should_error_on_out_of_bounds_offsets() {
+        // Given a record whose directory points past the payload
+        let mut bytes = BytesMut::from(&encode_record(&[(1, 1.into()), (2, 2.into())])[..]);
+        let second_offset = HEADER_BYTES + 1 + DIR_ENTRY_BYTES + 3;
+        bytes[second_offset..second_offset + 4].copy_from_slice(&100u32.to_le_bytes());
+        let (record, _) = ImprintRecord::read(bytes.freeze()).unwrap();
+
+        // Then accessing the field should fail instead of panicking
+        assert!(matches!(
+            record.get_value(2),
+            Err(ImprintError::InvalidOffset {
+                field_id: 2,
+                offset: 100
+            })
+        ));
+        assert!(record.get_value_ref(2).is_err());
+        assert_eq!(record.get_raw_bytes(1), None);
+        assert!(record.project(&[1]).is_err());
+        assert!(record.merge(&record).is_err());
+    }
+
+    #[test]
+    fn should_enforce_max_depth() {
+        // Given an array nested three levels deep
+        let nested: Value = Value::Array(vec![Value::Array(vec![vec![1, 2].into()])]);
+        let mut buf = BytesMut::new();
+        nested.write(&mut buf).unwrap();
+        let bytes = buf.freeze();
+
+        // When reading it with a depth limit of two
+        let limits = DecodeLimits {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let result = Value::read_with_limits(TypeCode::Array, bytes.clone(), &limits);
+
+        // Then it should fail with a limit error
+        assert!(matches!(
+            result,
+            Err(ImprintError::LimitExceeded {
+                limit: "max_depth",
+                max: 2
+            })
+        ));
+
+        // And a limit of three should be enough
+        let limits = DecodeLimits {
+            max_depth: 3,
+            ..Default::default()
+        };
+        let (value, _) = Value::read_with_limits(TypeCode::Array, bytes, &limits).unwrap();
+        assert_eq!(value, nested);
+    }
+
+    #[test]
+    fn should_error_instead_of_overflowing_the_stack() {
+        // Given arrays nested far deeper than the default limit
+        let mut buf = BytesMut::new();
+        for _ in 0..100_000 {
+            buf.put_u8(1);
+            buf.put_u8(TypeCode::Array as u8);
+        }
+        buf.put_u8(0);
+        let bytes = buf.freeze();
+
+        // Then reading it should fail with a limit error
+        assert!(matches!(
+            Value::read(TypeCode::Array, bytes.clone()),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_depth",
+                ..
+            })
+        ));
+        assert!(matches!(
+            crate::ValueRef::read(TypeCode::Array, &bytes),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_depth",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_enforce_element_and_length_limits() {
+        let limits = DecodeLimits {
+            max_elements: 2,
+            max_bytes_len: 4,
+            ..Default::default()
+        };
+
+        // Given an array and a map with three elements
+        let mut array = BytesMut::new();
+        Value::from(vec![1, 2, 3]).write(&mut array).unwrap();
+        let mut map = BytesMut::new();
+        Value::from(HashMap::from([(1, 1), (2, 2), (3, 3)]))
+            .write(&mut map)
+            .unwrap();
+
+        // Then they should exceed the element limit
+        assert!(matches!(
+            Value::read_with_limits(TypeCode::Array, array.freeze(), &limits),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_elements",
+                ..
+            })
+        ));
+        assert!(matches!(
+            Value::read_with_limits(TypeCode::Map, map.freeze(), &limits),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_elements",
+                ..
+            })
+        ));
+
+        // And a five byte string should exceed the length limit
+        let mut string = BytesMut::new();
+        Value::from("hello").write(&mut string).unwrap();
+        assert!(matches!(
+            Value::read_with_limits(TypeCode::String, string.freeze(), &limits),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_bytes_len",
+                ..
+            })
+        ));
+
+        // And a record with three fields should exceed the directory limit
+        let record = encode_record(&[(1, 1.into()), (2, 2.into()), (3, 3.into())]);
+        assert!(matches!(
+            ImprintRecord::read_with_limits(record, &limits),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_elements",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_enforce_allocation_limit() {
+        // Given an array claiming u32::MAX null elements in five bytes
+        let mut buf = BytesMut::new();
+        varint::encode(u32::MAX, &mut buf);
+        buf.put_u8(TypeCode::Null as u8);
+
+        // Then reading it with the default limits should fail instead of
+        // allocating hundreds of gigabytes
+        assert!(matches!(
+            Value::read(TypeCode::Array, buf.freeze()),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_allocation",
+                ..
+            })
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_never_panics_on_corrupted_records(
+            mutations in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            truncate in any::<prop::sample::Index>()
+        ) {
+            // Given a valid record with nested and variable width fields
+            let inner = encode_record(&[(1, "inner".into())]);
+            let (inner, _) = ImprintRecord::read(inner).unwrap();
+            let bytes = encode_record(&[
+                (1, 42.into()),
+                (2, "hello".into()),
+                (3, vec!["a", "b"].into()),
+                (4, HashMap::from([(1, 2i64)]).into()),
+                (5, inner.into()),
+            ]);
+
+            // When corrupting and truncating it
+            let mut corrupted = BytesMut::from(&bytes[..]);
+            for (index, byte) in mutations {
+                let index = index.index(corrupted.len());
+                corrupted[index] = byte;
+            }
+            corrupted.truncate(truncate.index(corrupted.len() + 1));
+
+            // Then reading and accessing every field must not panic
+            if let Ok((record, _)) = ImprintRecord::read(corrupted.freeze()) {
+                for entry in record.directory.iter() {
+                    let _ = record.get_value(entry.id);
+                    let _ = record.get_value_ref(entry.id);
+                    let _ = record.get_raw_bytes(entry.id);
+                }
+                let _ = record.project(&[1, 3, 5]);
+                let _ = record.merge(&record);
+            }
+        }
+    }
+
     #[test]
     fn test_duplicate_field_id() {
         let mut writer = ImprintWriter::new(SchemaId {
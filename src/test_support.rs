@@ -0,0 +1,43 @@
+//! Fixtures shared by the unit tests of each module
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{ImprintRecord, ImprintWriter, SchemaId, Value, Write};
+
+/// Build a record in fieldspace 1 holding the given fields
+pub(crate) fn create_record(fields: &[(u16, Value)]) -> ImprintRecord {
+    build_record(1, fields, &[])
+}
+
+/// Build a record in the given fieldspace holding the given fields
+pub(crate) fn create_record_in(fieldspace_id: u32, fields: &[(u16, Value)]) -> ImprintRecord {
+    build_record(fieldspace_id, fields, &[])
+}
+
+/// Build a partial update in fieldspace 1 that sets some fields and unsets
+/// others with tombstones
+pub(crate) fn create_update(set: &[(u16, Value)], unset: &[u16]) -> ImprintRecord {
+    build_record(1, set, unset)
+}
+
+/// Encode a record in fieldspace 1 holding the given fields
+pub(crate) fn encode_record(fields: &[(u16, Value)]) -> Bytes {
+    let mut buf = BytesMut::new();
+    build_record(1, fields, &[]).write(&mut buf).unwrap();
+    buf.freeze()
+}
+
+fn build_record(fieldspace_id: u32, set: &[(u16, Value)], unset: &[u16]) -> ImprintRecord {
+    let mut writer = ImprintWriter::new(SchemaId {
+        fieldspace_id,
+        schema_hash: 0,
+    })
+    .unwrap();
+    for (id, value) in set {
+        writer.add_field(*id, value.clone()).unwrap();
+    }
+    for id in unset {
+        writer.add_tombstone(*id).unwrap();
+    }
+    writer.build().unwrap()
+}
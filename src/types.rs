@@ -1,7 +1,11 @@
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
 
 use crate::error::ImprintError;
-use crate::serde::ValueRead;
+use crate::limits::DecodeLimits;
+use crate::serde::Read;
+use crate::value_ref::ValueRef;
 use bytes::Bytes;
 
 /// Magic byte that starts every Imprint record (ASCII 'I')
@@ -10,15 +14,31 @@ pub const MAGIC: u8 = 0x49;
 pub const VERSION: u8 = 0x01;
 
 /// Flags that control how to deserialize the record
-/// There are currently no flags but this is reserved
-/// for future use.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Flags(pub(crate) u8);
 
 impl Flags {
+    /// The record is a delta against a base record of the same fieldspace
+    pub const DELTA: Flags = Flags(0x02);
+
     pub fn new(flags: u8) -> Self {
         Self(flags)
     }
+
+    /// Whether all of the bits in `other` are set
+    pub fn contains(&self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// These flags with all of the bits in `other` cleared
+    pub fn without(&self, other: Flags) -> Flags {
+        Flags(self.0 & !other.0)
+    }
+
+    /// The raw flag bits
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
 }
 
 /// Type codes for field values
@@ -36,6 +56,9 @@ pub enum TypeCode {
     Array = 0x8,
     Map = 0x9,
     Row = 0xA,
+    /// Marks a deleted field in a directory. Tombstones have no payload and
+    /// cannot appear as array elements or map keys or values.
+    Tombstone = 0xB,
 }
 
 impl TypeCode {
@@ -65,6 +88,7 @@ impl TryFrom<u8> for TypeCode {
             0x8 => Ok(Self::Array),
             0x9 => Ok(Self::Map),
             0xA => Ok(Self::Row),
+            0xB => Ok(Self::Tombstone),
             _ => Err(ImprintError::InvalidFieldType(value)),
         }
     }
@@ -206,7 +230,10 @@ impl PartialEq<MapKey> for Value {
 }
 
 /// A subset of `Value` that’s valid as a map key.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// Keys are ordered numerically for integers and lexicographically by their
+/// bytes for `Bytes` and `String`, which is the order map entries are encoded in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum MapKey {
     Int32(i32),
     Int64(i64),
@@ -295,12 +322,43 @@ pub struct DirectoryEntry {
 }
 
 /// A schema identifier consisting of a fieldspace ID and schema hash
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SchemaId {
     pub fieldspace_id: u32,
     pub schema_hash: u32,
 }
 
+impl SchemaId {
+    /// Builds the schema id of a field directory within the given fieldspace
+    pub fn for_directory(fieldspace_id: u32, directory: &[DirectoryEntry]) -> Self {
+        Self {
+            fieldspace_id,
+            schema_hash: schema_hash(directory),
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+
+/// Compute the schema hash of a field directory.
+///
+/// The hash is a 32-bit FNV-1a over the field ids and type codes of the
+/// directory entries (offsets are ignored), so any two records containing the
+/// same fields with the same types share a schema hash regardless of the
+/// values they hold.
+pub fn schema_hash(directory: &[DirectoryEntry]) -> u32 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for entry in directory {
+        let [lo, hi] = entry.id.to_le_bytes();
+        for byte in [lo, hi, entry.type_code as u8] {
+            hash ^= byte as u32;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
 /// The header of an Imprint record
 #[derive(Debug, Clone, PartialEq)]
 pub struct Header {
@@ -313,37 +371,268 @@ pub struct Header {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImprintRecord {
     pub(crate) header: Header,
-    pub(crate) directory: Vec<DirectoryEntry>,
+    pub(crate) directory: Arc<[DirectoryEntry]>,
     pub(crate) payload: Bytes,
 }
 
 impl ImprintRecord {
+    /// Assemble a record from a sorted directory and its payload, computing
+    /// the schema hash and payload size from them
+    pub(crate) fn from_parts(
+        flags: Flags,
+        fieldspace_id: u32,
+        directory: Vec<DirectoryEntry>,
+        payload: Bytes,
+    ) -> Self {
+        Self {
+            header: Header {
+                flags,
+                schema_id: SchemaId::for_directory(fieldspace_id, &directory),
+                payload_size: payload.len() as u32,
+            },
+            directory: directory.into(),
+            payload,
+        }
+    }
+
+    /// The schema id of this record
+    pub fn schema_id(&self) -> SchemaId {
+        self.header.schema_id
+    }
+
+    /// The flags of this record
+    pub fn flags(&self) -> Flags {
+        self.header.flags
+    }
+
+    /// Iterate over the `(id, type_code)` of each field in the directory, in field ID order
+    pub fn fields(&self) -> impl ExactSizeIterator<Item = (u16, TypeCode)> + '_ {
+        self.directory.iter().map(|e| (e.id, e.type_code))
+    }
+
+    /// Whether the record contains a field with the given ID that isn't a tombstone
+    pub fn contains(&self, field_id: u16) -> bool {
+        self.live_index(field_id).is_some()
+    }
+
+    /// Whether the record holds a tombstone for the given field ID
+    pub fn is_tombstone(&self, field_id: u16) -> bool {
+        self.directory
+            .binary_search_by_key(&field_id, |e| e.id)
+            .is_ok_and(|idx| self.directory[idx].type_code == TypeCode::Tombstone)
+    }
+
+    /// The number of fields in the record, including tombstones
+    pub fn field_count(&self) -> usize {
+        self.directory.len()
+    }
+
+    /// The encoded payload holding the values of all fields
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
     /// Get a value by field ID, deserializing it on demand
     pub fn get_value(&self, field_id: u16) -> Result<Option<Value>, ImprintError> {
-        match self.directory.binary_search_by_key(&field_id, |e| e.id) {
-            Ok(idx) => {
+        self.get_value_with_limits(field_id, &DecodeLimits::default())
+    }
+
+    /// Get a value by field ID, deserializing it on demand while enforcing the given limits
+    pub fn get_value_with_limits(
+        &self,
+        field_id: u16,
+        limits: &DecodeLimits,
+    ) -> Result<Option<Value>, ImprintError> {
+        match self.live_index(field_id) {
+            Some(idx) => {
                 let entry = &self.directory[idx];
+                if entry.offset as usize > self.payload.len() {
+                    return Err(ImprintError::InvalidOffset {
+                        field_id,
+                        offset: entry.offset,
+                    });
+                }
                 let value_bytes = self.payload.slice(entry.offset as usize..);
-                let (value, _) = Value::read(entry.type_code, value_bytes)?;
+                let (value, _) = Value::read_with_limits(entry.type_code, value_bytes, limits)?;
                 Ok(Some(value))
             }
-            Err(_) => Ok(None),
+            None => Ok(None),
+        }
+    }
+
+    /// Get an `Int32` field, erroring if the field has a different type
+    pub fn get_i32(&self, field_id: u16) -> Result<Option<i32>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::Int32)? {
+            Some(ValueRef::Int32(v)) => Ok(Some(v)),
+            other => Self::unexpected(field_id, TypeCode::Int32, other),
+        }
+    }
+
+    /// Get an `Int64` field, erroring if the field has a different type
+    pub fn get_i64(&self, field_id: u16) -> Result<Option<i64>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::Int64)? {
+            Some(ValueRef::Int64(v)) => Ok(Some(v)),
+            other => Self::unexpected(field_id, TypeCode::Int64, other),
+        }
+    }
+
+    /// Get a `Float64` field, erroring if the field has a different type
+    pub fn get_f64(&self, field_id: u16) -> Result<Option<f64>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::Float64)? {
+            Some(ValueRef::Float64(v)) => Ok(Some(v)),
+            other => Self::unexpected(field_id, TypeCode::Float64, other),
+        }
+    }
+
+    /// Get a `Bool` field, erroring if the field has a different type
+    pub fn get_bool(&self, field_id: u16) -> Result<Option<bool>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::Bool)? {
+            Some(ValueRef::Bool(v)) => Ok(Some(v)),
+            other => Self::unexpected(field_id, TypeCode::Bool, other),
+        }
+    }
+
+    /// Get a `String` field borrowed from the payload, erroring if the field
+    /// has a different type
+    pub fn get_str(&self, field_id: u16) -> Result<Option<&str>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::String)? {
+            Some(ValueRef::String(v)) => Ok(Some(v)),
+            other => Self::unexpected(field_id, TypeCode::String, other),
+        }
+    }
+
+    /// Get a `Bytes` field as a slice of the payload, erroring if the field
+    /// has a different type
+    pub fn get_bytes(&self, field_id: u16) -> Result<Option<Bytes>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::Bytes)? {
+            Some(ValueRef::Bytes(v)) => Ok(Some(self.payload.slice_ref(v))),
+            other => Self::unexpected(field_id, TypeCode::Bytes, other),
+        }
+    }
+
+    /// Get a nested `Row` field, sharing the payload of this record, erroring
+    /// if the field has a different type
+    pub fn get_row(&self, field_id: u16) -> Result<Option<ImprintRecord>, ImprintError> {
+        match self.get_typed(field_id, TypeCode::Row)? {
+            Some(ValueRef::Row(row)) => {
+                let (record, _) = ImprintRecord::read(self.payload.slice_ref(row.as_bytes()))?;
+                Ok(Some(record))
+            }
+            other => Self::unexpected(field_id, TypeCode::Row, other),
+        }
+    }
+
+    /// Like [`get_i32`](Self::get_i32), but errors if the field is missing
+    pub fn require_i32(&self, field_id: u16) -> Result<i32, ImprintError> {
+        self.get_i32(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Like [`get_i64`](Self::get_i64), but errors if the field is missing
+    pub fn require_i64(&self, field_id: u16) -> Result<i64, ImprintError> {
+        self.get_i64(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Like [`get_f64`](Self::get_f64), but errors if the field is missing
+    pub fn require_f64(&self, field_id: u16) -> Result<f64, ImprintError> {
+        self.get_f64(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Like [`get_bool`](Self::get_bool), but errors if the field is missing
+    pub fn require_bool(&self, field_id: u16) -> Result<bool, ImprintError> {
+        self.get_bool(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Like [`get_str`](Self::get_str), but errors if the field is missing
+    pub fn require_str(&self, field_id: u16) -> Result<&str, ImprintError> {
+        self.get_str(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Like [`get_bytes`](Self::get_bytes), but errors if the field is missing
+    pub fn require_bytes(&self, field_id: u16) -> Result<Bytes, ImprintError> {
+        self.get_bytes(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Like [`get_row`](Self::get_row), but errors if the field is missing
+    pub fn require_row(&self, field_id: u16) -> Result<ImprintRecord, ImprintError> {
+        self.get_row(field_id)?
+            .ok_or(ImprintError::FieldNotFound(field_id.into()))
+    }
+
+    /// Get a borrowed view of a field, checking its type code against the
+    /// directory before decoding anything
+    fn get_typed(
+        &self,
+        field_id: u16,
+        expected: TypeCode,
+    ) -> Result<Option<ValueRef<'_>>, ImprintError> {
+        match self.live_index(field_id) {
+            Some(idx) if self.directory[idx].type_code != expected => {
+                Err(ImprintError::TypeMismatch {
+                    field_id,
+                    expected,
+                    actual: self.directory[idx].type_code,
+                })
+            }
+            Some(_) => self.get_value_ref(field_id),
+            None => Ok(None),
+        }
+    }
+
+    /// The directory index of a field that isn't a tombstone
+    pub(crate) fn live_index(&self, field_id: u16) -> Option<usize> {
+        self.directory
+            .binary_search_by_key(&field_id, |e| e.id)
+            .ok()
+            .filter(|&idx| self.directory[idx].type_code != TypeCode::Tombstone)
+    }
+
+    fn unexpected<T>(
+        field_id: u16,
+        expected: TypeCode,
+        value: Option<ValueRef<'_>>,
+    ) -> Result<Option<T>, ImprintError> {
+        match value {
+            Some(value) => Err(ImprintError::TypeMismatch {
+                field_id,
+                expected,
+                actual: value.type_code(),
+            }),
+            None => Ok(None),
         }
     }
 
     /// Get the raw bytes for a field without deserializing
+    ///
+    /// Returns `None` if the field is absent or its directory entry points
+    /// outside of the payload.
     pub fn get_raw_bytes(&self, field_id: u16) -> Option<Bytes> {
-        let idx = self
-            .directory
-            .binary_search_by_key(&field_id, |e| e.id)
-            .ok()?;
+        let idx = self.live_index(field_id)?;
+        let range = self.field_range(idx).ok()?;
+        Some(self.payload.slice(range))
+    }
+
+    /// The payload range of the directory entry at `idx`, which spans from its
+    /// offset up to the offset of the next entry (or the end of the payload)
+    pub(crate) fn field_range(&self, idx: usize) -> Result<Range<usize>, ImprintError> {
         let entry = &self.directory[idx];
         let start = entry.offset as usize;
-        let next_offset = self.directory[idx + 1..]
+        let end = self.directory[idx + 1..]
             .first()
             .map(|e| e.offset as usize)
             .unwrap_or(self.payload.len());
-        Some(self.payload.slice(start..next_offset))
+        if start > end || end > self.payload.len() {
+            return Err(ImprintError::InvalidOffset {
+                field_id: entry.id,
+                offset: entry.offset,
+            });
+        }
+        Ok(start..end)
     }
 }
 
@@ -360,4 +649,195 @@ mod tests {
     fn test_value_eq_map_key() {
         assert!(Value::String("foo".into()) == MapKey::String("foo".into()));
     }
+
+    #[test]
+    fn should_expose_record_metadata() {
+        // Given a record with a few fields
+        let mut writer = crate::ImprintWriter::new(SchemaId {
+            fieldspace_id: 7,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(3, "three".into()).unwrap();
+        writer.add_field(1, 1i64.into()).unwrap();
+        let record = writer.build().unwrap();
+
+        // Then its metadata should be readable without decoding any values
+        assert_eq!(record.schema_id(), record.header.schema_id);
+        assert_eq!(record.schema_id().fieldspace_id, 7);
+        assert_eq!(record.flags().bits(), 0);
+        assert_eq!(record.field_count(), 2);
+        assert_eq!(
+            record.fields().collect::<Vec<_>>(),
+            vec![(1, TypeCode::Int64), (3, TypeCode::String)]
+        );
+        assert!(record.contains(1));
+        assert!(record.contains(3));
+        assert!(!record.contains(2));
+        assert_eq!(record.payload().len(), 8 + 1 + "three".len());
+    }
+
+    fn create_typed_record() -> ImprintRecord {
+        let mut inner = crate::ImprintWriter::new(SchemaId {
+            fieldspace_id: 2,
+            schema_hash: 0,
+        })
+        .unwrap();
+        inner.add_field(1, "inner".into()).unwrap();
+
+        let mut writer = crate::ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, 42i64.into()).unwrap();
+        writer.add_field(3, 4.2f64.into()).unwrap();
+        writer.add_field(4, true.into()).unwrap();
+        writer.add_field(5, "hello".into()).unwrap();
+        writer.add_field(6, vec![1u8, 2].into()).unwrap();
+        writer.add_field(7, inner.build().unwrap().into()).unwrap();
+        writer.build().unwrap()
+    }
+
+    #[test]
+    fn should_read_typed_fields() {
+        // Given a record with one field of each supported type
+        let record = create_typed_record();
+
+        // Then each typed getter should return its field
+        assert_eq!(record.get_i32(1).unwrap(), Some(42));
+        assert_eq!(record.get_i64(2).unwrap(), Some(42));
+        assert_eq!(record.get_f64(3).unwrap(), Some(4.2));
+        assert_eq!(record.get_bool(4).unwrap(), Some(true));
+        assert_eq!(record.get_str(5).unwrap(), Some("hello"));
+        assert_eq!(
+            record.get_bytes(6).unwrap(),
+            Some(Bytes::from_static(&[1, 2]))
+        );
+        let row = record.get_row(7).unwrap().unwrap();
+        assert_eq!(row.get_str(1).unwrap(), Some("inner"));
+
+        // And missing fields should be absent
+        assert_eq!(record.get_i32(99).unwrap(), None);
+        assert_eq!(record.get_str(99).unwrap(), None);
+    }
+
+    #[test]
+    fn should_error_on_typed_field_mismatch() {
+        // Given a record with an Int32 field
+        let record = create_typed_record();
+
+        // When reading it as a different type
+        let result = record.get_i64(1);
+
+        // Then the error should name the field and its actual type
+        assert!(matches!(
+            result,
+            Err(ImprintError::TypeMismatch {
+                field_id: 1,
+                expected: TypeCode::Int64,
+                actual: TypeCode::Int32,
+            })
+        ));
+        assert!(matches!(
+            record.get_str(6),
+            Err(ImprintError::TypeMismatch {
+                field_id: 6,
+                actual: TypeCode::Bytes,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_error_when_required_field_is_missing() {
+        // Given a record without field 99
+        let record = create_typed_record();
+
+        // Then requiring it should report the missing field
+        assert!(matches!(
+            record.require_i32(99),
+            Err(ImprintError::FieldNotFound(99))
+        ));
+        assert!(matches!(
+            record.require_row(99),
+            Err(ImprintError::FieldNotFound(99))
+        ));
+
+        // And requiring present fields should return their values
+        assert_eq!(record.require_i32(1).unwrap(), 42);
+        assert_eq!(record.require_str(5).unwrap(), "hello");
+    }
+
+    fn entry(id: u16, type_code: TypeCode, offset: u32) -> DirectoryEntry {
+        DirectoryEntry {
+            id,
+            type_code,
+            offset,
+        }
+    }
+
+    #[test]
+    fn should_hash_same_fields_to_same_schema() {
+        // Given two directories with the same fields but different offsets
+        let a = [entry(1, TypeCode::Int32, 0), entry(2, TypeCode::String, 4)];
+        let b = [
+            entry(1, TypeCode::Int32, 0),
+            entry(2, TypeCode::String, 4096),
+        ];
+
+        // Then they should share a schema hash
+        assert_eq!(schema_hash(&a), schema_hash(&b));
+    }
+
+    #[test]
+    fn should_hash_different_fields_to_different_schemas() {
+        let base = [entry(1, TypeCode::Int32, 0), entry(2, TypeCode::String, 4)];
+        let other_type = [entry(1, TypeCode::Int64, 0), entry(2, TypeCode::String, 8)];
+        let other_id = [entry(1, TypeCode::Int32, 0), entry(3, TypeCode::String, 4)];
+        let subset = [entry(1, TypeCode::Int32, 0)];
+
+        assert_ne!(schema_hash(&base), schema_hash(&other_type));
+        assert_ne!(schema_hash(&base), schema_hash(&other_id));
+        assert_ne!(schema_hash(&base), schema_hash(&subset));
+        assert_ne!(schema_hash(&subset), schema_hash(&[]));
+    }
+
+    #[test]
+    fn should_report_tombstones_as_absent() {
+        // Given a record with a tombstone
+        let mut writer = crate::ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_tombstone(2).unwrap();
+        writer.add_field(3, "three".into()).unwrap();
+        let record = writer.build().unwrap();
+
+        // Then the tombstone should be in the directory without a payload
+        assert_eq!(
+            record.fields().collect::<Vec<_>>(),
+            vec![
+                (1, TypeCode::Int32),
+                (2, TypeCode::Tombstone),
+                (3, TypeCode::String)
+            ]
+        );
+        assert_eq!(record.payload().len(), 4 + 1 + "three".len());
+
+        // And the field should read as absent rather than null
+        assert!(record.is_tombstone(2));
+        assert!(!record.contains(2));
+        assert_eq!(record.get_value(2).unwrap(), None);
+        assert!(record.get_value_ref(2).unwrap().is_none());
+        assert_eq!(record.get_i32(2).unwrap(), None);
+        assert_eq!(record.get_raw_bytes(2), None);
+
+        // And the surrounding fields should be unaffected
+        assert_eq!(record.get_value(3).unwrap(), Some("three".into()));
+        record.validate(crate::ValidationLevel::Deep).unwrap();
+    }
 }
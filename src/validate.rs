@@ -0,0 +1,381 @@
+use std::fmt;
+
+use crate::{
+    error::ImprintError,
+    limits::{DEFAULT_MAX_DEPTH, DecodeLimits},
+    types::{ImprintRecord, TypeCode, Value},
+};
+
+/// How thoroughly [`ImprintRecord::validate`] checks a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationLevel {
+    /// Only check the directory: ids must be strictly increasing and offsets
+    /// must be non-decreasing and within the payload
+    Cheap,
+    /// Check the directory and decode every value (including nested rows),
+    /// checking that each value spans exactly the gap to the next offset
+    Deep,
+}
+
+/// The reason a field failed validation
+#[derive(Debug)]
+pub enum Violation {
+    /// The field id is smaller than the id of the entry before it
+    UnsortedFieldId,
+    /// The field id is the same as the id of the entry before it
+    DuplicateFieldId,
+    /// The offset points past the end of the payload
+    OffsetPastPayload { payload_size: usize },
+    /// The offset is smaller than the offset of the entry before it
+    DecreasingOffset,
+    /// The decoded value doesn't span the gap to the next offset
+    LengthMismatch { expected: usize, actual: usize },
+    /// The value could not be decoded
+    Malformed(Box<ImprintError>),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsortedFieldId => write!(f, "field ids are not sorted"),
+            Self::DuplicateFieldId => write!(f, "duplicate field id"),
+            Self::OffsetPastPayload { payload_size } => {
+                write!(
+                    f,
+                    "offset is past the end of the {payload_size} byte payload"
+                )
+            }
+            Self::DecreasingOffset => write!(f, "offset is before the previous field's offset"),
+            Self::LengthMismatch { expected, actual } => {
+                write!(f, "value is {actual} bytes but the field spans {expected}")
+            }
+            Self::Malformed(e) => write!(f, "value could not be decoded: {e}"),
+        }
+    }
+}
+
+impl ImprintRecord {
+    /// Check the structure of this record, returning an
+    /// [`ImprintError::InvalidField`] naming the first offending field and
+    /// its offset.
+    ///
+    /// Lookups binary search the directory and assume it is well formed, so
+    /// records from untrusted producers should be validated before use.
+    ///
+    /// Values may nest at most [`DEFAULT_MAX_DEPTH`] rows, arrays and maps
+    /// deep, counting across nested rows, and deeper values fail with
+    /// [`ImprintError::LimitExceeded`].
+    pub fn validate(&self, level: ValidationLevel) -> Result<(), ImprintError> {
+        // nested rows are checked against the depth left over from their
+        // parents, so report the limit for the record as a whole
+        self.validate_with_depth(level, DEFAULT_MAX_DEPTH)
+            .map_err(|e| match e {
+                ImprintError::LimitExceeded {
+                    limit: "max_depth", ..
+                } => ImprintError::LimitExceeded {
+                    limit: "max_depth",
+                    max: DEFAULT_MAX_DEPTH,
+                },
+                e => e,
+            })
+    }
+
+    /// Validate this record, allowing its values to nest at most `max_depth`
+    /// levels deep
+    fn validate_with_depth(
+        &self,
+        level: ValidationLevel,
+        max_depth: usize,
+    ) -> Result<(), ImprintError> {
+        let invalid = |idx: usize, violation| {
+            let entry = &self.directory[idx];
+            Err(ImprintError::InvalidField {
+                field_id: entry.id,
+                offset: entry.offset,
+                violation,
+            })
+        };
+
+        for (idx, entry) in self.directory.iter().enumerate() {
+            if entry.offset as usize > self.payload.len() {
+                return invalid(
+                    idx,
+                    Violation::OffsetPastPayload {
+                        payload_size: self.payload.len(),
+                    },
+                );
+            }
+            let Some(previous) = idx.checked_sub(1).map(|i| &self.directory[i]) else {
+                continue;
+            };
+            if entry.id < previous.id {
+                return invalid(idx, Violation::UnsortedFieldId);
+            }
+            if entry.id == previous.id {
+                return invalid(idx, Violation::DuplicateFieldId);
+            }
+            if entry.offset < previous.offset {
+                return invalid(idx, Violation::DecreasingOffset);
+            }
+        }
+
+        if level == ValidationLevel::Cheap {
+            return Ok(());
+        }
+
+        for (idx, entry) in self.directory.iter().enumerate() {
+            let range = self.field_range(idx)?;
+            let expected = range.len();
+            if entry.type_code == TypeCode::Tombstone {
+                if expected != 0 {
+                    return invalid(
+                        idx,
+                        Violation::LengthMismatch {
+                            expected,
+                            actual: 0,
+                        },
+                    );
+                }
+                continue;
+            }
+            let limits = DecodeLimits {
+                max_depth,
+                ..DecodeLimits::default()
+            };
+            let decoded =
+                Value::read_with_limits(entry.type_code, self.payload.slice(range), &limits)
+                    .and_then(|(value, actual)| validate_nested(&value, max_depth).map(|_| actual));
+            match decoded {
+                Ok(actual) if actual != expected => {
+                    return invalid(idx, Violation::LengthMismatch { expected, actual });
+                }
+                Ok(_) => {}
+                Err(e @ ImprintError::LimitExceeded { .. }) => return Err(e),
+                Err(e) => return invalid(idx, Violation::Malformed(Box::new(e))),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deeply validate any rows nested within a decoded value, which may itself
+/// nest at most `max_depth` levels deep
+fn validate_nested(value: &Value, max_depth: usize) -> Result<(), ImprintError> {
+    // decoding the value already checked that it fits within `max_depth`
+    let inner_depth = max_depth.saturating_sub(1);
+    match value {
+        Value::Row(record) => record.validate_with_depth(ValidationLevel::Deep, inner_depth),
+        Value::Array(values) => values
+            .iter()
+            .try_for_each(|v| validate_nested(v, inner_depth)),
+        Value::Map(map) => map
+            .values()
+            .try_for_each(|v| validate_nested(v, inner_depth)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ImprintWriter,
+        types::{DirectoryEntry, SchemaId},
+    };
+
+    fn create_test_record() -> ImprintRecord {
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, "hello".into()).unwrap();
+        writer.add_field(3, true.into()).unwrap();
+        writer.build().unwrap()
+    }
+
+    fn with_directory(record: &ImprintRecord, directory: &[(u16, TypeCode, u32)]) -> ImprintRecord {
+        let directory: Vec<_> = directory
+            .iter()
+            .map(|&(id, type_code, offset)| DirectoryEntry {
+                id,
+                type_code,
+                offset,
+            })
+            .collect();
+        ImprintRecord {
+            header: record.header.clone(),
+            directory: directory.into(),
+            payload: record.payload.clone(),
+        }
+    }
+
+    fn assert_invalid(
+        record: &ImprintRecord,
+        level: ValidationLevel,
+        expected_id: u16,
+        expected_offset: u32,
+        check: impl Fn(&Violation) -> bool,
+    ) {
+        match record.validate(level) {
+            Err(ImprintError::InvalidField {
+                field_id,
+                offset,
+                violation,
+            }) => {
+                assert_eq!(field_id, expected_id);
+                assert_eq!(offset, expected_offset);
+                assert!(check(&violation), "unexpected violation {violation:?}");
+            }
+            other => panic!("expected invalid field, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn should_accept_well_formed_records() {
+        // Given a record built by the writer
+        let record = create_test_record();
+
+        // Then it should pass both levels of validation
+        record.validate(ValidationLevel::Cheap).unwrap();
+        record.validate(ValidationLevel::Deep).unwrap();
+    }
+
+    #[test]
+    fn should_reject_unsorted_and_duplicate_ids() {
+        let record = create_test_record();
+
+        let unsorted = with_directory(
+            &record,
+            &[(2, TypeCode::Int32, 0), (1, TypeCode::String, 4)],
+        );
+        assert_invalid(&unsorted, ValidationLevel::Cheap, 1, 4, |v| {
+            matches!(v, Violation::UnsortedFieldId)
+        });
+
+        let duplicate = with_directory(
+            &record,
+            &[(1, TypeCode::Int32, 0), (1, TypeCode::String, 4)],
+        );
+        assert_invalid(&duplicate, ValidationLevel::Cheap, 1, 4, |v| {
+            matches!(v, Violation::DuplicateFieldId)
+        });
+    }
+
+    #[test]
+    fn should_reject_bad_offsets() {
+        let record = create_test_record();
+
+        let past_payload = with_directory(
+            &record,
+            &[(1, TypeCode::Int32, 0), (2, TypeCode::String, 99)],
+        );
+        assert_invalid(&past_payload, ValidationLevel::Cheap, 2, 99, |v| {
+            matches!(v, Violation::OffsetPastPayload { payload_size: 11 })
+        });
+
+        let decreasing = with_directory(
+            &record,
+            &[(1, TypeCode::Int32, 4), (2, TypeCode::String, 0)],
+        );
+        assert_invalid(&decreasing, ValidationLevel::Cheap, 2, 0, |v| {
+            matches!(v, Violation::DecreasingOffset)
+        });
+    }
+
+    #[test]
+    fn should_reject_length_mismatches_only_when_deep() {
+        // Given a directory that drops the string, leaving a gap after the int
+        let record = create_test_record();
+        let gapped = with_directory(&record, &[(1, TypeCode::Int32, 0), (3, TypeCode::Bool, 10)]);
+
+        // Then cheap validation should pass
+        gapped.validate(ValidationLevel::Cheap).unwrap();
+
+        // But deep validation should catch the mismatch
+        assert_invalid(&gapped, ValidationLevel::Deep, 1, 0, |v| {
+            matches!(
+                v,
+                Violation::LengthMismatch {
+                    expected: 10,
+                    actual: 4
+                }
+            )
+        });
+    }
+
+    #[test]
+    fn should_reject_undecodable_values_when_deep() {
+        // Given a directory claiming the int field is a bool
+        let record = create_test_record();
+        let mistyped = with_directory(
+            &record,
+            &[
+                (1, TypeCode::Bool, 0),
+                (2, TypeCode::String, 4),
+                (3, TypeCode::Bool, 10),
+            ],
+        );
+
+        // Then deep validation should report the field that can't be decoded
+        assert_invalid(&mistyped, ValidationLevel::Deep, 1, 0, |v| {
+            matches!(v, Violation::Malformed(_))
+        });
+    }
+
+    #[test]
+    fn should_validate_nested_rows() {
+        // Given a record nesting a row with a corrupted directory
+        let inner = create_test_record();
+        let corrupted =
+            with_directory(&inner, &[(2, TypeCode::Int32, 0), (1, TypeCode::String, 4)]);
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(9, corrupted.into()).unwrap();
+        let outer = writer.build().unwrap();
+
+        // Then deep validation should find the nested violation
+        outer.validate(ValidationLevel::Cheap).unwrap();
+        assert_invalid(
+            &outer,
+            ValidationLevel::Deep,
+            9,
+            0,
+            |v| matches!(v, Violation::Malformed(e) if matches!(**e, ImprintError::InvalidField { field_id: 1, .. })),
+        );
+    }
+
+    #[test]
+    fn should_bound_depth_across_nested_rows() {
+        // Given records nesting rows within rows
+        let nest = |levels: usize| {
+            (0..levels).fold(create_test_record(), |inner, _| {
+                let mut writer = ImprintWriter::new(SchemaId {
+                    fieldspace_id: 1,
+                    schema_hash: 0,
+                })
+                .unwrap();
+                writer.add_field(1, inner.into()).unwrap();
+                writer.build().unwrap()
+            })
+        };
+
+        // Then nesting within the depth limit should validate
+        nest(DEFAULT_MAX_DEPTH)
+            .validate(ValidationLevel::Deep)
+            .unwrap();
+
+        // But nesting past it should fail rather than recurse further
+        assert!(matches!(
+            nest(DEFAULT_MAX_DEPTH + 1).validate(ValidationLevel::Deep),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_depth",
+                max: DEFAULT_MAX_DEPTH
+            })
+        ));
+    }
+}
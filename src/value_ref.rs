@@ -0,0 +1,1138 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use bytes::{Buf, Bytes};
+
+use crate::{
+    error::ImprintError,
+    limits::{DEFAULT_MAX_DEPTH, DecodeLimits},
+    serde::{DIR_ENTRY_BYTES, HEADER_BYTES, Read, read_directory_entry, read_header},
+    types::{DirectoryEntry, Flags, Header, ImprintRecord, MapKey, SchemaId, TypeCode, Value},
+    varint,
+};
+
+/// A borrowed view of a value inside an encoded payload.
+///
+/// Strings and bytes borrow directly from the payload, while arrays, maps and
+/// nested rows are decoded lazily as they are iterated or accessed, so reading
+/// a `ValueRef` never allocates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRef<'a> {
+    Null,
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    Float32(f32),
+    Float64(f64),
+    Bytes(&'a [u8]),
+    String(&'a str),
+    Array(ArrayRef<'a>),
+    Map(MapRef<'a>),
+    Row(RowRef<'a>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Read a value with a known type code from the start of a slice, returning
+    /// the value and number of bytes read
+    ///
+    /// Arrays and maps may nest at most [`DEFAULT_MAX_DEPTH`] levels deep.
+    pub fn read(type_code: TypeCode, bytes: &'a [u8]) -> Result<(Self, usize), ImprintError> {
+        Self::read_with_limits(type_code, bytes, &DecodeLimits::default())
+    }
+
+    /// Read a value with a known type code from the start of a slice, enforcing
+    /// the given limits, and returning the value and number of bytes read
+    ///
+    /// Arrays, maps and rows read from the value keep enforcing the same limits.
+    /// Since views never allocate, `max_allocation` doesn't apply.
+    pub fn read_with_limits(
+        type_code: TypeCode,
+        bytes: &'a [u8],
+        limits: &DecodeLimits,
+    ) -> Result<(Self, usize), ImprintError> {
+        let mut buf = bytes;
+        let value = match type_code {
+            TypeCode::Null => return Ok((ValueRef::Null, 0)),
+            TypeCode::Bool => {
+                ensure_remaining(buf, 1)?;
+                match buf.get_u8() {
+                    0 => ValueRef::Bool(false),
+                    1 => ValueRef::Bool(true),
+                    _ => return Err(ImprintError::SchemaError("invalid boolean value".into())),
+                }
+            }
+            TypeCode::Int32 => {
+                ensure_remaining(buf, 4)?;
+                ValueRef::Int32(buf.get_i32_le())
+            }
+            TypeCode::Int64 => {
+                ensure_remaining(buf, 8)?;
+                ValueRef::Int64(buf.get_i64_le())
+            }
+            TypeCode::Float32 => {
+                ensure_remaining(buf, 4)?;
+                ValueRef::Float32(buf.get_f32_le())
+            }
+            TypeCode::Float64 => {
+                ensure_remaining(buf, 8)?;
+                ValueRef::Float64(buf.get_f64_le())
+            }
+            TypeCode::Bytes => {
+                let (v, size) = read_length_prefixed(bytes, limits)?;
+                return Ok((ValueRef::Bytes(v), size));
+            }
+            TypeCode::String => {
+                let (v, size) = read_length_prefixed(bytes, limits)?;
+                let s = std::str::from_utf8(v).map_err(|_| ImprintError::InvalidUtf8String)?;
+                return Ok((ValueRef::String(s), size));
+            }
+            TypeCode::Array => {
+                let (array, size) = ArrayRef::read(bytes, limits.max_depth, limits)?;
+                return Ok((ValueRef::Array(array), size));
+            }
+            TypeCode::Map => {
+                let (map, size) = MapRef::read(bytes, limits.max_depth, limits)?;
+                return Ok((ValueRef::Map(map), size));
+            }
+            TypeCode::Row => {
+                let (row, size) = RowRef::read_with_limits(bytes, limits)?;
+                return Ok((ValueRef::Row(row), size));
+            }
+            TypeCode::Tombstone => return Err(ImprintError::InvalidFieldType(type_code as u8)),
+        };
+        Ok((value, bytes.len() - buf.len()))
+    }
+
+    pub fn type_code(&self) -> TypeCode {
+        match self {
+            Self::Null => TypeCode::Null,
+            Self::Bool(_) => TypeCode::Bool,
+            Self::Int32(_) => TypeCode::Int32,
+            Self::Int64(_) => TypeCode::Int64,
+            Self::Float32(_) => TypeCode::Float32,
+            Self::Float64(_) => TypeCode::Float64,
+            Self::Bytes(_) => TypeCode::Bytes,
+            Self::String(_) => TypeCode::String,
+            Self::Array(_) => TypeCode::Array,
+            Self::Map(_) => TypeCode::Map,
+            Self::Row(_) => TypeCode::Row,
+        }
+    }
+
+    /// Whether every map within this value, including maps nested in arrays,
+    /// maps and rows, has its entries in canonical key order without duplicates
+    ///
+    /// Values nesting more than [`DEFAULT_MAX_DEPTH`] rows, arrays and maps
+    /// deep fail with [`ImprintError::LimitExceeded`].
+    pub fn is_canonical(&self) -> Result<bool, ImprintError> {
+        self.is_canonical_within(DEFAULT_MAX_DEPTH)
+    }
+
+    /// Check canonical order within a value nesting at most `depth` rows,
+    /// arrays and maps deep
+    fn is_canonical_within(&self, depth: usize) -> Result<bool, ImprintError> {
+        match self {
+            Self::Array(array) => {
+                check_depth(depth, DEFAULT_MAX_DEPTH)?;
+                for value in array.iter() {
+                    if !value?.is_canonical_within(depth - 1)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Self::Map(map) => map.is_canonical_within(depth),
+            Self::Row(row) => {
+                check_depth(depth, DEFAULT_MAX_DEPTH)?;
+                for idx in 0..row.field_count() {
+                    let entry = read_directory_entry(&row.directory[idx * DIR_ENTRY_BYTES..])?;
+                    if let Some(value) = read_field(&entry, row.payload, &row.limits)?
+                        && !value.is_canonical_within(depth - 1)?
+                    {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// Decode this view into an owned `Value`
+    pub fn to_value(&self) -> Result<Value, ImprintError> {
+        Ok(match self {
+            Self::Null => Value::Null,
+            Self::Bool(v) => Value::Bool(*v),
+            Self::Int32(v) => Value::Int32(*v),
+            Self::Int64(v) => Value::Int64(*v),
+            Self::Float32(v) => Value::Float32(*v),
+            Self::Float64(v) => Value::Float64(*v),
+            Self::Bytes(v) => Value::Bytes(v.to_vec()),
+            Self::String(v) => Value::String(v.to_string()),
+            Self::Array(array) => Value::Array(
+                array
+                    .iter()
+                    .map(|v| v?.to_value())
+                    .collect::<Result<_, _>>()?,
+            ),
+            Self::Map(map) => {
+                let mut values = HashMap::with_capacity(map.len());
+                for entry in map.iter() {
+                    let (key, value) = entry?;
+                    values.insert(MapKey::try_from(key.to_value()?)?, value.to_value()?);
+                }
+                Value::Map(values)
+            }
+            Self::Row(row) => Value::Row(Box::new(row.to_record()?)),
+        })
+    }
+}
+
+/// A lazily decoded view of an encoded array
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrayRef<'a> {
+    len: u32,
+    element_type: Option<TypeCode>,
+    elements: &'a [u8],
+    limits: DecodeLimits,
+}
+
+impl<'a> ArrayRef<'a> {
+    fn read(
+        bytes: &'a [u8],
+        depth: usize,
+        limits: &DecodeLimits,
+    ) -> Result<(Self, usize), ImprintError> {
+        check_depth(depth, limits.max_depth)?;
+        let (len, len_size) = varint::decode_slice(bytes)?;
+        if len == 0 {
+            let array = Self {
+                len,
+                element_type: None,
+                elements: &[],
+                limits: *limits,
+            };
+            return Ok((array, len_size));
+        }
+
+        limits.check_elements(len)?;
+        let header_size = len_size + 1;
+        ensure_remaining(bytes, header_size)?;
+        let element_type = TypeCode::try_from(bytes[len_size])?;
+        let elements_size =
+            elements_len(element_type, len, &bytes[header_size..], depth - 1, limits)?;
+        let array = Self {
+            len,
+            element_type: Some(element_type),
+            elements: &bytes[header_size..header_size + elements_size],
+            limits: *limits,
+        };
+        Ok((array, header_size + elements_size))
+    }
+
+    /// The number of elements in the array
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The type code shared by all elements, or `None` for an empty array
+    pub fn element_type(&self) -> Option<TypeCode> {
+        self.element_type
+    }
+
+    /// Iterate over the elements of the array, decoding each on demand
+    pub fn iter(&self) -> ArrayIter<'a> {
+        ArrayIter {
+            element_type: self.element_type.unwrap_or(TypeCode::Null),
+            remaining: self.len,
+            bytes: self.elements,
+            limits: self.limits,
+        }
+    }
+
+    /// Get the element at `index`, skipping over the preceding elements
+    /// without decoding them
+    pub fn get(&self, index: usize) -> Result<Option<ValueRef<'a>>, ImprintError> {
+        let Some(element_type) = self.element_type else {
+            return Ok(None);
+        };
+        if index >= self.len() {
+            return Ok(None);
+        }
+
+        let start = match element_type.fixed_width() {
+            Some(width) => index * width,
+            None => elements_len(
+                element_type,
+                index as u32,
+                self.elements,
+                self.limits.max_depth,
+                &self.limits,
+            )?,
+        };
+        let (value, _) =
+            ValueRef::read_with_limits(element_type, &self.elements[start..], &self.limits)?;
+        Ok(Some(value))
+    }
+}
+
+impl<'a> IntoIterator for ArrayRef<'a> {
+    type Item = Result<ValueRef<'a>, ImprintError>;
+    type IntoIter = ArrayIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of an [`ArrayRef`]
+#[derive(Debug, Clone)]
+pub struct ArrayIter<'a> {
+    element_type: TypeCode,
+    remaining: u32,
+    bytes: &'a [u8],
+    limits: DecodeLimits,
+}
+
+impl<'a> Iterator for ArrayIter<'a> {
+    type Item = Result<ValueRef<'a>, ImprintError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match ValueRef::read_with_limits(self.element_type, self.bytes, &self.limits) {
+            Ok((value, size)) => {
+                self.remaining -= 1;
+                self.bytes = &self.bytes[size..];
+                Some(Ok(value))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+/// A lazily decoded view of an encoded map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapRef<'a> {
+    len: u32,
+    key_type: Option<TypeCode>,
+    value_type: Option<TypeCode>,
+    entries: &'a [u8],
+    limits: DecodeLimits,
+}
+
+impl<'a> MapRef<'a> {
+    fn read(
+        bytes: &'a [u8],
+        depth: usize,
+        limits: &DecodeLimits,
+    ) -> Result<(Self, usize), ImprintError> {
+        check_depth(depth, limits.max_depth)?;
+        let (len, len_size) = varint::decode_slice(bytes)?;
+        if len == 0 {
+            let map = Self {
+                len,
+                key_type: None,
+                value_type: None,
+                entries: &[],
+                limits: *limits,
+            };
+            return Ok((map, len_size));
+        }
+
+        limits.check_elements(len)?;
+        let header_size = len_size + 2;
+        ensure_remaining(bytes, header_size)?;
+        let key_type = TypeCode::try_from(bytes[len_size])?;
+        if !matches!(
+            key_type,
+            TypeCode::Int32 | TypeCode::Int64 | TypeCode::Bytes | TypeCode::String
+        ) {
+            return Err(ImprintError::InvalidFieldType(key_type as u8));
+        }
+        let value_type = TypeCode::try_from(bytes[len_size + 1])?;
+        // every key takes at least one byte, so a longer map can't fit
+        ensure_remaining(bytes, header_size + len as usize)?;
+        let entries_size = entries_len(
+            key_type,
+            value_type,
+            len,
+            &bytes[header_size..],
+            depth - 1,
+            limits,
+        )?;
+        let map = Self {
+            len,
+            key_type: Some(key_type),
+            value_type: Some(value_type),
+            entries: &bytes[header_size..header_size + entries_size],
+            limits: *limits,
+        };
+        Ok((map, header_size + entries_size))
+    }
+
+    /// The number of entries in the map
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The type code shared by all keys, or `None` for an empty map
+    pub fn key_type(&self) -> Option<TypeCode> {
+        self.key_type
+    }
+
+    /// The type code shared by all values, or `None` for an empty map
+    pub fn value_type(&self) -> Option<TypeCode> {
+        self.value_type
+    }
+
+    /// Whether the entries are in strictly ascending key order, which is how
+    /// maps are written, and the values are themselves canonical
+    pub fn is_canonical(&self) -> Result<bool, ImprintError> {
+        self.is_canonical_within(DEFAULT_MAX_DEPTH)
+    }
+
+    /// Check canonical order within a map nesting at most `depth` rows,
+    /// arrays and maps deep, counting itself
+    fn is_canonical_within(&self, depth: usize) -> Result<bool, ImprintError> {
+        check_depth(depth, DEFAULT_MAX_DEPTH)?;
+        let mut previous: Option<ValueRef<'a>> = None;
+        for entry in self.iter() {
+            let (key, value) = entry?;
+            if let Some(previous) = &previous
+                && cmp_keys(previous, &key) != Ordering::Less
+            {
+                return Ok(false);
+            }
+            if !value.is_canonical_within(depth - 1)? {
+                return Ok(false);
+            }
+            previous = Some(key);
+        }
+        Ok(true)
+    }
+
+    /// Get the value for `key`, skipping over the entries before it without
+    /// copying their values
+    pub fn get(&self, key: &MapKey) -> Result<Option<ValueRef<'a>>, ImprintError> {
+        if self.key_type != Some(key.type_code()) {
+            return Ok(None);
+        }
+        for entry in self.iter() {
+            let (candidate, value) = entry?;
+            let found = match (&candidate, key) {
+                (ValueRef::Int32(a), MapKey::Int32(b)) => a == b,
+                (ValueRef::Int64(a), MapKey::Int64(b)) => a == b,
+                (ValueRef::Bytes(a), MapKey::Bytes(b)) => a == b,
+                (ValueRef::String(a), MapKey::String(b)) => a == b,
+                _ => false,
+            };
+            if found {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterate over the entries of the map in encoded order, decoding each on demand
+    pub fn iter(&self) -> MapIter<'a> {
+        MapIter {
+            key_type: self.key_type.unwrap_or(TypeCode::Null),
+            value_type: self.value_type.unwrap_or(TypeCode::Null),
+            remaining: self.len,
+            bytes: self.entries,
+            limits: self.limits,
+        }
+    }
+}
+
+impl<'a> IntoIterator for MapRef<'a> {
+    type Item = Result<(ValueRef<'a>, ValueRef<'a>), ImprintError>;
+    type IntoIter = MapIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a [`MapRef`]
+#[derive(Debug, Clone)]
+pub struct MapIter<'a> {
+    key_type: TypeCode,
+    value_type: TypeCode,
+    remaining: u32,
+    bytes: &'a [u8],
+    limits: DecodeLimits,
+}
+
+impl<'a> MapIter<'a> {
+    fn read_entry(&mut self) -> Result<(ValueRef<'a>, ValueRef<'a>), ImprintError> {
+        let (key, key_size) = ValueRef::read_with_limits(self.key_type, self.bytes, &self.limits)?;
+        let (value, value_size) =
+            ValueRef::read_with_limits(self.value_type, &self.bytes[key_size..], &self.limits)?;
+        self.bytes = &self.bytes[key_size + value_size..];
+        Ok((key, value))
+    }
+}
+
+impl<'a> Iterator for MapIter<'a> {
+    type Item = Result<(ValueRef<'a>, ValueRef<'a>), ImprintError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.read_entry();
+        self.remaining = if entry.is_ok() { self.remaining - 1 } else { 0 };
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+/// A view of a nested record whose fields are decoded on demand
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowRef<'a> {
+    header: Header,
+    directory: &'a [u8],
+    payload: &'a [u8],
+    raw: &'a [u8],
+    limits: DecodeLimits,
+}
+
+impl<'a> RowRef<'a> {
+    /// Read a nested record from the start of a slice, returning the row and
+    /// number of bytes read
+    pub fn read(bytes: &'a [u8]) -> Result<(Self, usize), ImprintError> {
+        Self::read_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// Read a nested record from the start of a slice, enforcing the given
+    /// limits on it and on the values read from it
+    pub fn read_with_limits(
+        bytes: &'a [u8],
+        limits: &DecodeLimits,
+    ) -> Result<(Self, usize), ImprintError> {
+        let header = read_header(bytes)?;
+        let (count, count_size) = varint::decode_slice(&bytes[HEADER_BYTES..])?;
+        limits.check_elements(count)?;
+        let directory_start = HEADER_BYTES + count_size;
+        let payload_start = directory_start + count as usize * DIR_ENTRY_BYTES;
+        let end = payload_start + header.payload_size as usize;
+        ensure_remaining(bytes, end)?;
+
+        let row = Self {
+            header,
+            directory: &bytes[directory_start..payload_start],
+            payload: &bytes[payload_start..end],
+            raw: &bytes[..end],
+            limits: *limits,
+        };
+        Ok((row, end))
+    }
+
+    pub fn schema_id(&self) -> SchemaId {
+        self.header.schema_id
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.header.flags
+    }
+
+    /// The number of fields in the row
+    pub fn field_count(&self) -> usize {
+        self.directory.len() / DIR_ENTRY_BYTES
+    }
+
+    /// The encoded payload holding the values of all fields
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// The complete encoded row, including its header and directory
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Whether the row contains a field with the given ID that isn't a tombstone
+    pub fn contains(&self, field_id: u16) -> Result<bool, ImprintError> {
+        Ok(self
+            .find(field_id)?
+            .is_some_and(|e| e.type_code != TypeCode::Tombstone))
+    }
+
+    /// Get a borrowed view of a field's value, decoding it on demand
+    pub fn get_value_ref(&self, field_id: u16) -> Result<Option<ValueRef<'a>>, ImprintError> {
+        match self.find(field_id)? {
+            Some(entry) => read_field(&entry, self.payload, &self.limits),
+            None => Ok(None),
+        }
+    }
+
+    /// Parse the entries of the row's directory
+    pub(crate) fn directory_entries(&self) -> Result<Vec<DirectoryEntry>, ImprintError> {
+        self.directory
+            .chunks_exact(DIR_ENTRY_BYTES)
+            .map(read_directory_entry)
+            .collect()
+    }
+
+    /// Decode this row into an owned record
+    pub fn to_record(&self) -> Result<ImprintRecord, ImprintError> {
+        let (record, _) = ImprintRecord::read(Bytes::copy_from_slice(self.raw))?;
+        Ok(record)
+    }
+
+    /// Binary search the encoded directory without parsing it
+    fn find(&self, field_id: u16) -> Result<Option<DirectoryEntry>, ImprintError> {
+        let mut low = 0;
+        let mut high = self.field_count();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let entry = read_directory_entry(&self.directory[mid * DIR_ENTRY_BYTES..])?;
+            match entry.id.cmp(&field_id) {
+                Ordering::Equal => return Ok(Some(entry)),
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Compare two map keys of the same type in canonical order, consistent with
+/// the ordering of `MapKey`
+fn cmp_keys(a: &ValueRef<'_>, b: &ValueRef<'_>) -> Ordering {
+    match (a, b) {
+        (ValueRef::Int32(a), ValueRef::Int32(b)) => a.cmp(b),
+        (ValueRef::Int64(a), ValueRef::Int64(b)) => a.cmp(b),
+        (ValueRef::Bytes(a), ValueRef::Bytes(b)) => a.cmp(b),
+        (ValueRef::String(a), ValueRef::String(b)) => a.cmp(b),
+        _ => (a.type_code() as u8).cmp(&(b.type_code() as u8)),
+    }
+}
+
+impl ImprintRecord {
+    /// Whether every map in the record is canonically ordered, meaning the
+    /// record is byte-for-byte equal to any other encoding of the same row
+    pub fn is_canonical(&self) -> Result<bool, ImprintError> {
+        for entry in self.directory.iter() {
+            if let Some(value) = read_field(entry, &self.payload, &DecodeLimits::default())?
+                && !value.is_canonical()?
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Get a borrowed view of a field's value without copying it out of the payload
+    pub fn get_value_ref(&self, field_id: u16) -> Result<Option<ValueRef<'_>>, ImprintError> {
+        self.get_value_ref_with_limits(field_id, &DecodeLimits::default())
+    }
+
+    /// Get a borrowed view of a field's value while enforcing the given limits
+    pub fn get_value_ref_with_limits(
+        &self,
+        field_id: u16,
+        limits: &DecodeLimits,
+    ) -> Result<Option<ValueRef<'_>>, ImprintError> {
+        match self.directory.binary_search_by_key(&field_id, |e| e.id) {
+            Ok(idx) => read_field(&self.directory[idx], &self.payload, limits),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Read the value of a directory entry, or `None` if it is a tombstone
+fn read_field<'a>(
+    entry: &DirectoryEntry,
+    payload: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<Option<ValueRef<'a>>, ImprintError> {
+    if entry.type_code == TypeCode::Tombstone {
+        return Ok(None);
+    }
+    let value_bytes = payload
+        .get(entry.offset as usize..)
+        .ok_or(ImprintError::InvalidOffset {
+            field_id: entry.id,
+            offset: entry.offset,
+        })?;
+    let (value, _) = ValueRef::read_with_limits(entry.type_code, value_bytes, limits)?;
+    Ok(Some(value))
+}
+
+/// Compute the encoded size of a value nesting at most `depth` arrays and maps
+/// deep without decoding it
+pub(crate) fn encoded_len(
+    type_code: TypeCode,
+    bytes: &[u8],
+    depth: usize,
+    limits: &DecodeLimits,
+) -> Result<usize, ImprintError> {
+    if let Some(width) = type_code.fixed_width() {
+        ensure_remaining(bytes, width)?;
+        return Ok(width);
+    }
+
+    match type_code {
+        TypeCode::Bytes | TypeCode::String => Ok(read_length_prefixed(bytes, limits)?.1),
+        TypeCode::Array => Ok(ArrayRef::read(bytes, depth, limits)?.1),
+        TypeCode::Map => Ok(MapRef::read(bytes, depth, limits)?.1),
+        TypeCode::Row => Ok(RowRef::read_with_limits(bytes, limits)?.1),
+        TypeCode::Tombstone => Err(ImprintError::InvalidFieldType(type_code as u8)),
+        _ => Ok(0),
+    }
+}
+
+/// Compute the encoded size of `count` consecutive values of the same type
+fn elements_len(
+    type_code: TypeCode,
+    count: u32,
+    bytes: &[u8],
+    depth: usize,
+    limits: &DecodeLimits,
+) -> Result<usize, ImprintError> {
+    if type_code == TypeCode::Null {
+        return Ok(0);
+    }
+    if let Some(width) = type_code.fixed_width() {
+        let size = width * count as usize;
+        ensure_remaining(bytes, size)?;
+        return Ok(size);
+    }
+
+    let mut size = 0;
+    for _ in 0..count {
+        size += encoded_len(type_code, &bytes[size..], depth, limits)?;
+    }
+    Ok(size)
+}
+
+/// Compute the encoded size of `count` consecutive map entries
+fn entries_len(
+    key_type: TypeCode,
+    value_type: TypeCode,
+    count: u32,
+    bytes: &[u8],
+    depth: usize,
+    limits: &DecodeLimits,
+) -> Result<usize, ImprintError> {
+    let mut size = 0;
+    for _ in 0..count {
+        size += encoded_len(key_type, &bytes[size..], depth, limits)?;
+        size += encoded_len(value_type, &bytes[size..], depth, limits)?;
+    }
+    Ok(size)
+}
+
+/// Read a varint length followed by that many bytes
+fn read_length_prefixed<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<(&'a [u8], usize), ImprintError> {
+    let (len, len_size) = varint::decode_slice(bytes)?;
+    limits.check_bytes_len(len)?;
+    let end = len_size + len as usize;
+    ensure_remaining(bytes, end)?;
+    Ok((&bytes[len_size..end], end))
+}
+
+fn check_depth(depth: usize, max_depth: usize) -> Result<(), ImprintError> {
+    if depth == 0 {
+        return Err(ImprintError::LimitExceeded {
+            limit: "max_depth",
+            max: max_depth,
+        });
+    }
+    Ok(())
+}
+
+fn ensure_remaining(bytes: &[u8], needed: usize) -> Result<(), ImprintError> {
+    if bytes.len() < needed {
+        return Err(ImprintError::BufferUnderflow {
+            needed,
+            available: bytes.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ImprintWriter;
+    use crate::serde::ValueRead;
+    use crate::test_support::{create_record, encode_record};
+
+    fn create_test_record() -> ImprintRecord {
+        let mut inner = ImprintWriter::new(SchemaId {
+            fieldspace_id: 2,
+            schema_hash: 0,
+        })
+        .unwrap();
+        inner.add_field(1, "zip".into()).unwrap();
+        inner.add_field(2, 94107.into()).unwrap();
+
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(1, 42.into()).unwrap();
+        writer.add_field(2, "hello".into()).unwrap();
+        writer.add_field(3, vec![1u8, 2, 3].into()).unwrap();
+        writer.add_field(4, vec!["a", "bc", "def"].into()).unwrap();
+        writer
+            .add_field(5, HashMap::from([("k", 1i64)]).into())
+            .unwrap();
+        writer.add_field(6, inner.build().unwrap().into()).unwrap();
+        writer.add_field(7, Value::Null).unwrap();
+        writer.build().unwrap()
+    }
+
+    #[test]
+    fn should_borrow_strings_and_bytes_from_payload() {
+        // Given a record with string and bytes fields
+        let record = create_test_record();
+
+        // When reading them as borrowed views
+        let Some(ValueRef::String(s)) = record.get_value_ref(2).unwrap() else {
+            panic!("expected string");
+        };
+        let Some(ValueRef::Bytes(b)) = record.get_value_ref(3).unwrap() else {
+            panic!("expected bytes");
+        };
+
+        // Then they should point into the record's payload
+        assert_eq!(s, "hello");
+        assert_eq!(b, &[1, 2, 3]);
+        let payload = record.payload().as_ptr_range();
+        assert!(payload.contains(&s.as_ptr()));
+        assert!(payload.contains(&b.as_ptr()));
+    }
+
+    #[test]
+    fn should_iterate_arrays_and_maps_lazily() {
+        // Given a record with array and map fields
+        let record = create_test_record();
+
+        // When reading the array
+        let Some(ValueRef::Array(array)) = record.get_value_ref(4).unwrap() else {
+            panic!("expected array");
+        };
+
+        // Then its elements should be available by iteration and index
+        assert_eq!(array.len(), 3);
+        assert_eq!(array.element_type(), Some(TypeCode::String));
+        let elements: Vec<_> = array.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            elements,
+            vec![
+                ValueRef::String("a"),
+                ValueRef::String("bc"),
+                ValueRef::String("def")
+            ]
+        );
+        assert_eq!(array.get(2).unwrap(), Some(ValueRef::String("def")));
+        assert_eq!(array.get(3).unwrap(), None);
+
+        // And the map entries should be readable in place
+        let Some(ValueRef::Map(map)) = record.get_value_ref(5).unwrap() else {
+            panic!("expected map");
+        };
+        let entries: Vec<_> = map.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(entries, vec![(ValueRef::String("k"), ValueRef::Int64(1))]);
+    }
+
+    #[test]
+    fn should_read_nested_row_fields_on_demand() {
+        // Given a record with a nested row
+        let record = create_test_record();
+
+        // When reading the nested row
+        let Some(ValueRef::Row(row)) = record.get_value_ref(6).unwrap() else {
+            panic!("expected row");
+        };
+
+        // Then its fields should be readable without decoding the row
+        assert_eq!(row.schema_id().fieldspace_id, 2);
+        assert_eq!(row.field_count(), 2);
+        assert_eq!(row.get_value_ref(1).unwrap(), Some(ValueRef::String("zip")));
+        assert_eq!(row.get_value_ref(2).unwrap(), Some(ValueRef::Int32(94107)));
+        assert_eq!(row.get_value_ref(3).unwrap(), None);
+    }
+
+    #[test]
+    fn should_match_owned_values() {
+        // Given a record with fields of every kind
+        let record = create_test_record();
+
+        // Then each borrowed view should convert to the owned value
+        for (id, _) in record.fields() {
+            let borrowed = record.get_value_ref(id).unwrap().unwrap();
+            assert_eq!(
+                Some(borrowed.to_value().unwrap()),
+                record.get_value(id).unwrap(),
+                "field {} should match",
+                id
+            );
+        }
+        assert_eq!(record.get_value_ref(99).unwrap(), None);
+    }
+
+    #[test]
+    fn should_report_encoded_size() {
+        // Given encoded arrays of fixed and variable width elements
+        let mut buf = bytes::BytesMut::new();
+        crate::Write::write(&Value::from(vec![1i64, 2, 3]), &mut buf).unwrap();
+        crate::Write::write(&Value::from(vec!["x", "yz"]), &mut buf).unwrap();
+        let bytes = buf.freeze();
+
+        // When reading them back to back
+        let (first, first_size) = ValueRef::read(TypeCode::Array, &bytes).unwrap();
+        let (second, second_size) = ValueRef::read(TypeCode::Array, &bytes[first_size..]).unwrap();
+
+        // Then the sizes should match the owned reader
+        let (_, owned_size) = Value::read(TypeCode::Array, bytes.clone()).unwrap();
+        assert_eq!(first_size, owned_size);
+        assert_eq!(first_size + second_size, bytes.len());
+        assert_eq!(first.to_value().unwrap(), Value::from(vec![1i64, 2, 3]));
+        assert_eq!(second.to_value().unwrap(), Value::from(vec!["x", "yz"]));
+    }
+
+    #[test]
+    fn should_detect_non_canonical_maps() {
+        // Given maps encoded by hand with descending and duplicate keys
+        let descending = [
+            2u8,
+            TypeCode::Int32 as u8,
+            TypeCode::Bool as u8,
+            2,
+            0,
+            0,
+            0,
+            1,
+            1,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let duplicate = [
+            2u8,
+            TypeCode::Int32 as u8,
+            TypeCode::Bool as u8,
+            1,
+            0,
+            0,
+            0,
+            1,
+            1,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let ascending = [
+            2u8,
+            TypeCode::Int32 as u8,
+            TypeCode::Bool as u8,
+            1,
+            0,
+            0,
+            0,
+            1,
+            2,
+            0,
+            0,
+            0,
+            0,
+        ];
+
+        // Then only the ascending map should be canonical
+        let check = |bytes: &[u8]| {
+            let (map, _) = ValueRef::read(TypeCode::Map, bytes).unwrap();
+            map.is_canonical().unwrap()
+        };
+        assert!(!check(&descending));
+        assert!(!check(&duplicate));
+        assert!(check(&ascending));
+    }
+
+    #[test]
+    fn should_order_string_keys_by_bytes() {
+        // Given a map with string keys whose insertion order isn't sorted
+        let map = HashMap::from([("b", 1), ("a", 2), ("ab", 3), ("B", 4)]);
+        let mut writer = ImprintWriter::new(SchemaId {
+            fieldspace_id: 1,
+            schema_hash: 0,
+        })
+        .unwrap();
+        writer.add_field(1, map.into()).unwrap();
+        let record = writer.build().unwrap();
+
+        // When reading the map back in encoded order
+        let Some(ValueRef::Map(map)) = record.get_value_ref(1).unwrap() else {
+            panic!("expected map");
+        };
+        let keys: Vec<_> = map.iter().map(|e| e.unwrap().0).collect();
+
+        // Then the keys should be in byte order
+        assert_eq!(
+            keys,
+            vec![
+                ValueRef::String("B"),
+                ValueRef::String("a"),
+                ValueRef::String("ab"),
+                ValueRef::String("b")
+            ]
+        );
+        assert!(record.is_canonical().unwrap());
+    }
+
+    #[test]
+    fn should_error_on_truncated_values() {
+        // Given a string whose length prefix overruns the buffer
+        let bytes = [5u8, b'a', b'b'];
+
+        // Then reading it should fail instead of panicking
+        assert!(matches!(
+            ValueRef::read(TypeCode::String, &bytes),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+        assert!(matches!(
+            ValueRef::read(TypeCode::Array, &[3u8]),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+    }
+
+    #[test]
+    fn should_enforce_limits_on_borrowed_reads() {
+        let limits = DecodeLimits {
+            max_elements: 2,
+            max_bytes_len: 4,
+            ..Default::default()
+        };
+
+        // Given a record with a three element array, a five byte string and a
+        // nested row holding another five byte string
+        let inner = create_record(&[(1, "hello".into())]);
+        let record = create_record(&[
+            (1, vec![1, 2, 3].into()),
+            (2, "hello".into()),
+            (3, inner.into()),
+        ]);
+
+        // Then borrowed reads should enforce the element and length limits
+        assert!(matches!(
+            record.get_value_ref_with_limits(1, &limits),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_elements",
+                ..
+            })
+        ));
+        assert!(matches!(
+            record.get_value_ref_with_limits(2, &limits),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_bytes_len",
+                ..
+            })
+        ));
+
+        // And fields read lazily from a nested row should keep the limits
+        let Some(ValueRef::Row(row)) = record.get_value_ref_with_limits(3, &limits).unwrap() else {
+            panic!("expected row");
+        };
+        assert!(matches!(
+            row.get_value_ref(1),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_bytes_len",
+                ..
+            })
+        ));
+        assert_eq!(
+            record.get_value_ref(2).unwrap(),
+            Some(ValueRef::String("hello"))
+        );
+    }
+
+    #[test]
+    fn should_report_offsets_past_the_payload() {
+        // Given an encoded row whose only directory entry points past its payload
+        let mut bytes = encode_record(&[(1, 42.into())]).to_vec();
+        bytes[HEADER_BYTES + 1 + 3..HEADER_BYTES + 1 + DIR_ENTRY_BYTES]
+            .copy_from_slice(&99u32.to_le_bytes());
+        let (row, _) = RowRef::read(&bytes).unwrap();
+
+        // Then reading the field should report the offset like the owned reads
+        assert!(matches!(
+            row.get_value_ref(1),
+            Err(ImprintError::InvalidOffset {
+                field_id: 1,
+                offset: 99
+            })
+        ));
+    }
+
+    #[test]
+    fn should_reject_maps_with_more_entries_than_bytes() {
+        // Given maps declaring u32::MAX entries with null or integer keys
+        let null_keys = [0xffu8, 0xff, 0xff, 0xff, 0x0f, 0x00, 0x00];
+        let int_keys = [0xffu8, 0xff, 0xff, 0xff, 0x0f, 0x02, 0x00];
+
+        // Then null keys should be rejected as invalid map keys
+        assert!(matches!(
+            ValueRef::read(TypeCode::Map, &null_keys),
+            Err(ImprintError::InvalidFieldType(0))
+        ));
+        // And the entry count should be checked against the remaining bytes
+        assert!(matches!(
+            ValueRef::read(TypeCode::Map, &int_keys),
+            Err(ImprintError::BufferUnderflow { .. })
+        ));
+    }
+
+    #[test]
+    fn should_bound_depth_when_checking_canonical_order() {
+        // Given rows nested past the depth limit, which can be read lazily
+        let nested = (0..DEFAULT_MAX_DEPTH).fold(create_test_record(), |inner, _| {
+            let mut writer = ImprintWriter::new(SchemaId {
+                fieldspace_id: 1,
+                schema_hash: 0,
+            })
+            .unwrap();
+            writer.add_field(1, inner.into()).unwrap();
+            writer.build().unwrap()
+        });
+        let value = nested.get_value_ref(1).unwrap().unwrap();
+
+        // Then checking them should fail rather than recurse further
+        assert!(matches!(
+            value.is_canonical(),
+            Err(ImprintError::LimitExceeded {
+                limit: "max_depth",
+                ..
+            })
+        ));
+    }
+}
@@ -22,7 +22,12 @@ pub fn encode(value: u32, buf: &mut BytesMut) {
 }
 
 /// Decode a VarInt from the provided bytes, returning the value and number of bytes read
-pub fn decode(mut bytes: Bytes) -> Result<(u32, usize), ImprintError> {
+pub fn decode(bytes: Bytes) -> Result<(u32, usize), ImprintError> {
+    decode_slice(&bytes)
+}
+
+/// Decode a VarInt from the start of a slice, returning the value and number of bytes read
+pub(crate) fn decode_slice(mut bytes: &[u8]) -> Result<(u32, usize), ImprintError> {
     let mut result: u32 = 0;
     let mut shift = 0;
     let mut bytes_read = 0;
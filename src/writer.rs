@@ -4,17 +4,20 @@ use std::collections::BTreeMap;
 use crate::{
     error::ImprintError,
     serde::Write,
-    types::{DirectoryEntry, Flags, Header, ImprintRecord, SchemaId, Value},
+    types::{DirectoryEntry, Flags, ImprintRecord, SchemaId, TypeCode, Value},
 };
 
 /// A writer for constructing ImprintRecords by adding fields sequentially.
 pub struct ImprintWriter {
     schema_id: SchemaId,
-    fields: BTreeMap<u16, Value>, // keep fields in sorted order
+    fields: BTreeMap<u16, Option<Value>>, // keep fields in sorted order, None for tombstones
 }
 
 impl ImprintWriter {
     /// Creates a new ImprintWriter with the given schema ID.
+    ///
+    /// Only the fieldspace of the schema ID is kept, the schema hash of the
+    /// built record is computed from the fields that were added.
     pub fn new(schema_id: SchemaId) -> Result<Self, ImprintError> {
         Ok(Self {
             schema_id,
@@ -24,7 +27,14 @@ impl ImprintWriter {
 
     /// Adds a field to the record being built.
     pub fn add_field(&mut self, id: u16, value: Value) -> Result<(), ImprintError> {
-        self.fields.insert(id, value);
+        self.fields.insert(id, Some(value));
+        Ok(())
+    }
+
+    /// Adds a tombstone marking the field as deleted, so that merging the
+    /// built record removes the field from the result.
+    pub fn add_tombstone(&mut self, id: u16) -> Result<(), ImprintError> {
+        self.fields.insert(id, None);
         Ok(())
     }
 
@@ -36,22 +46,19 @@ impl ImprintWriter {
         for (&id, value) in &self.fields {
             directory.push(DirectoryEntry {
                 id,
-                type_code: value.type_code(),
+                type_code: value.as_ref().map_or(TypeCode::Tombstone, Value::type_code),
                 offset: payload.len() as u32,
             });
-            value.write(&mut payload)?;
+            if let Some(value) = value {
+                value.write(&mut payload)?;
+            }
         }
 
-        let header = Header {
-            flags: Flags::new(0), // Set appropriate flags as needed
-            schema_id: self.schema_id,
-            payload_size: payload.len() as u32,
-        };
-
-        Ok(ImprintRecord {
-            header,
+        Ok(ImprintRecord::from_parts(
+            Flags::new(0), // Set appropriate flags as needed
+            self.schema_id.fieldspace_id,
             directory,
-            payload: payload.freeze(),
-        })
+            payload.freeze(),
+        ))
     }
 }